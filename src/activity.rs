@@ -0,0 +1,32 @@
+use crate::config;
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+/// Once the log grows past this it is moved to `activity.log.1`, replacing
+/// the previous one, so at most twice this much is kept.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Appends the time, `action`, `id` and `description` as a tab separated line
+/// to `activity.log` next to the data file. It's a record to recover from, not
+/// something to stop editing over, so failures are ignored.
+pub fn record(action: &str, id: &str, description: &str) {
+    let _ = append(action, id, description);
+}
+
+fn append(action: &str, id: &str, description: &str) -> io::Result<()> {
+    let path = config::get_activity_log_path()?;
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > MAX_LOG_BYTES) {
+        fs::rename(&path, path.with_extension("log.1"))?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        action,
+        id,
+        description
+    )
+}
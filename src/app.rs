@@ -1,35 +1,123 @@
 use ratatui::text::Span;
-use ratatui::widgets::{ListState, Padding, Row, Table, TableState};
+use ratatui::widgets::{
+    ListState, Padding, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table, TableState,
+};
 use slotmap::{DefaultKey, SlotMap};
-use std::collections::HashSet;
-use std::sync::mpsc;
-use uuid::Uuid;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::activity;
 use crate::colors::Theme;
+use crate::config::{self, CompleteAction, Config, Storage};
+use crate::dates;
+use crate::import;
+use crate::keymap::{key_matches, KeyMap, Pane};
+use crate::query::Query;
 use crate::store::{self, SlotMapStore};
-use crate::store::{Store, TodoItem, WorkspaceItem};
+use crate::store::{Status, Store, TodoItem, WorkspaceItem};
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use ratatui::{
     layout::{Constraint, Layout, Position, Rect},
     style::{Color, Style, Stylize},
     text::Line,
-    widgets::{Block, List, ListItem},
+    widgets::{Block, Clear, List, ListItem},
     DefaultTerminal, Frame,
 };
 use tui_input::{backend::crossterm::EventHandler, Input};
 
+/// Used when `tick_rate_ms` isn't configured.
+const DEFAULT_TICK_RATE: Duration = Duration::from_secs(1);
+
+/// How often the event thread checks whether it has been paused.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the undo hint stays in the footer after completing a todo.
+const UNDO_COMPLETION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Used when `autosave_secs` isn't configured.
+const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Used when `quit_keys` isn't configured.
+const DEFAULT_QUIT_KEYS: [&str; 3] = ["q", "esc", "ctrl-c"];
+
+/// Root workspace that `complete_action: archive` moves completed todos to.
+const ARCHIVE_WORKSPACE: &str = "Archive";
+
+/// Cells in the progress bar after each workspace.
+const PROGRESS_WIDTH: usize = 5;
+
+/// Accent colors `C` cycles a workspace through.
+const WORKSPACE_COLORS: [&str; 6] = ["blue", "green", "yellow", "magenta", "cyan", "red"];
+
+/// The `?` overlay, the most used keys grouped by pane. The README has the
+/// full list.
+const HELP: [(&str, &[(&str, &str)]); 3] = [
+    (
+        "Anywhere",
+        &[
+            ("Tab", "switch pane"),
+            ("j | k", "move down | up"),
+            ("J | K", "move item down | up"),
+            ("h | l", "collapse | expand"),
+            ("zM | zR", "collapse | expand all"),
+            ("a | A", "add sibling | child"),
+            ("i", "edit"),
+            ("y | x", "yank | cut"),
+            ("p | P", "paste as sibling | child"),
+            ("<space>", "select multiple items"),
+            ("C-a | Esc", "select all | none"),
+            ("C-s", "sort"),
+            (":", "command, e.g. :w or :q"),
+            ("?", "show | hide this help"),
+            ("q | Esc", "quit"),
+        ],
+    ),
+    (
+        "Todos",
+        &[
+            ("c", "cycle todo, doing, done"),
+            ("u", "undo marking done"),
+            ("C", "hide | show done todos"),
+            ("+ | _", "raise | lower priority"),
+            ("> | <", "raise | lower effort"),
+            ("d | S", "set due date | snooze"),
+            ("t", "edit tags"),
+            ("M-l | M-h", "indent | outdent"),
+            ("f | F", "focus subtree | zoom out"),
+            ("gY", "copy as Markdown text"),
+            ("m", "move under another todo"),
+            ("M", "mark to move to a workspace"),
+            ("/ | C-f", "search | filter"),
+            ("n | N", "next | previous match"),
+        ],
+    ),
+    (
+        "Workspaces",
+        &[
+            ("c", "complete | reopen all todos"),
+            ("C", "cycle color"),
+            ("D", "duplicate"),
+            ("M", "move the marked todos here"),
+            ("zz", "collapse all but this path"),
+        ],
+    ),
+];
+
 #[derive(PartialEq, Eq)]
 enum Screen {
     Workspaces,
     Todos,
 }
 
-const PRIORITIES: [&'static str; 4] = ["󰯬", "󰯯", "󰯲", "󰯵"];
-const PRIORITY_COLORS: [Color; 4] = [Color::Green, Color::Yellow, Color::Magenta, Color::Red];
-
 pub struct App {
     theme: crate::colors::Theme,
+    config: Config,
     running: bool,
     slot_map_store: store::SlotMapStore,
     slot_tree_state: SlotTreeState,
@@ -43,8 +131,58 @@ pub struct App {
     clipboard_workspaces: Vec<DefaultKey>,
     search_mode: bool,
     search_str: String,
-    search_matches: Vec<DefaultKey>,
+    /// In the order `n` visits them.
+    search_matches: Vec<SearchMatch>,
+    /// Set for global searches to the workspaces whose subtree has a match.
+    search_workspace_hits: Option<HashSet<DefaultKey>>,
+    search_global: bool,
+    /// Typing into `filter_str` after `C-f`.
+    filter_mode: bool,
+    filter_str: String,
     current_match_index: usize,
+    pending_key: Option<char>,
+    last_opened_link: Option<(DefaultKey, usize)>,
+    /// Shown in the footer until the next key press.
+    status_message: Option<String>,
+    /// Index in `todo_tree` of the first todo drawn in the pane.
+    todo_offset: usize,
+    /// Where the panes were last drawn, to find the item under a click.
+    workspaces_area: PaneArea,
+    todos_area: PaneArea,
+    command_mode: bool,
+    command_str: String,
+    confirmation: Option<Confirmation>,
+    picker: Option<Picker>,
+    /// The keybindings overlay toggled with `?`.
+    show_help: bool,
+    /// Todo whose due or snooze date is being typed into `input` in the
+    /// footer.
+    date_editing: Option<(DefaultKey, DateField)>,
+    /// Todo whose tags are being typed into `input` in the footer.
+    tags_editing: Option<DefaultKey>,
+    last_completion: Option<Completion>,
+    /// Todos marked with `M` in the todos pane, moved to the workspace `M` is
+    /// pressed on next.
+    moving_todos: Vec<MovingTodo>,
+    /// Kept around because on X11 copied text is only served while the
+    /// clipboard handle is alive.
+    system_clipboard: Option<arboard::Clipboard>,
+    /// Whether parents show the ` done/total` badge of their children.
+    show_child_counts: bool,
+    /// When each expanded workspace was last on the selection's path.
+    workspace_accessed: HashMap<DefaultKey, Instant>,
+    /// Draw the panes without borders and padding, toggled with `:compact`.
+    compact: bool,
+    /// Set by `--safe`: edits stay in memory and nothing is written to disk.
+    safe_mode: bool,
+    /// Rebindings from `keys.yaml`.
+    keymap: KeyMap,
+    last_autosave: Instant,
+    /// Set by `:edit`, handled by the run loop since the terminal has to be
+    /// given up for the editor.
+    edit_requested: bool,
+    /// Stops the event thread from reading input while an editor runs.
+    events_paused: Arc<AtomicBool>,
 }
 
 enum SortingItem {
@@ -53,16 +191,99 @@ enum SortingItem {
     None,
 }
 
+/// Which date of a todo the footer prompt sets.
+#[derive(Clone, Copy)]
+enum DateField {
+    Due,
+    Snooze,
+}
+
+/// The last todo `c` marked as done, which `u` can revert for a few seconds.
+struct Completion {
+    key: DefaultKey,
+    previous: Status,
+    at: Instant,
+    /// Set when `complete_action` moved the todo, to put it back on undo.
+    moved: Option<CompletionMove>,
+}
+
+struct CompletionMove {
+    workspace: DefaultKey,
+    parent: Option<DefaultKey>,
+    index: usize,
+    /// The archive workspace the todo went to, if it left its siblings.
+    archive: Option<DefaultKey>,
+}
+
+/// A todo waiting to be moved to another workspace and where it sits now.
+struct MovingTodo {
+    key: DefaultKey,
+    workspace: DefaultKey,
+    parent: Option<DefaultKey>,
+}
+
+/// A pane as last drawn: its whole area, the part its items are listed in
+/// and the index of the first item shown.
+#[derive(Default, Clone, Copy)]
+struct PaneArea {
+    pane: Rect,
+    list: Rect,
+    offset: usize,
+}
+
+/// A search result. Workspace names only match during a global search.
+#[derive(Clone, Copy, PartialEq)]
+enum SearchMatch {
+    Workspace(DefaultKey),
+    Todo {
+        workspace: DefaultKey,
+        todo: DefaultKey,
+    },
+}
+
+/// Popup listing the todos of the workspace that `m` can move `moving` under.
+struct Picker {
+    moving: DefaultKey,
+    query: String,
+    /// Index into the filtered candidates.
+    selected: usize,
+}
+
+/// An action waiting on a yes/no answer in the footer.
+enum Confirmation {
+    /// Confirm-each `:%s` replacement. `remaining` holds the workspace and key
+    /// of every todo still to be asked about, the current one first.
+    Replace {
+        from: String,
+        to: String,
+        remaining: Vec<(DefaultKey, DefaultKey)>,
+        replaced: usize,
+    },
+    /// `x` on multi-selected todos.
+    CutTodos,
+    /// `x` on multi-selected workspaces.
+    CutWorkspaces,
+    /// `x` on a todo with children.
+    CutTodo(DefaultKey),
+    /// `x` on a workspace.
+    CutWorkspace(DefaultKey),
+}
+
 impl App {
     /// Construct a new instance of [`App`].
-    pub fn new(store: Store, theme: Theme) -> Self {
+    pub fn new(mut store: Store, theme: Theme, config: Config) -> Self {
         let (tx, rx) = mpsc::channel();
+        let reassigned = store.reassign_duplicate_ids();
         let slot_map_store = store::SlotMapStore::from_store(&store);
+        let slot_tree_state = SlotTreeState::new(&store, &slot_map_store, &config);
+        let show_child_counts = config.show_child_counts.unwrap_or(true);
+        let compact = config.compact.unwrap_or(false);
         Self {
             theme,
+            config,
             running: false,
             new_editing_id: None,
-            slot_tree_state: SlotTreeState::default(),
+            slot_tree_state,
             slot_map_store,
             input: Input::default(),
             sorting: SortingItem::None,
@@ -74,8 +295,66 @@ impl App {
             search_mode: false,
             search_str: String::new(),
             search_matches: Vec::new(),
+            search_workspace_hits: None,
+            search_global: false,
+            filter_mode: false,
+            filter_str: String::new(),
             current_match_index: 0,
+            pending_key: None,
+            last_opened_link: None,
+            status_message: duplicate_ids_message(reassigned),
+            todo_offset: 0,
+            workspaces_area: PaneArea::default(),
+            todos_area: PaneArea::default(),
+            command_mode: false,
+            command_str: String::new(),
+            confirmation: None,
+            picker: None,
+            show_help: false,
+            date_editing: None,
+            tags_editing: None,
+            last_completion: None,
+            moving_todos: Vec::new(),
+            system_clipboard: None,
+            show_child_counts,
+            compact,
+            workspace_accessed: HashMap::new(),
+            safe_mode: false,
+            keymap: KeyMap::default(),
+            last_autosave: Instant::now(),
+            edit_requested: false,
+            events_paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Never write to disk, not even the activity log. Whoever runs the app
+    /// has to skip saving the returned store as well.
+    pub fn with_safe_mode(mut self, safe_mode: bool) -> Self {
+        self.safe_mode = safe_mode;
+        self
+    }
+
+    pub fn with_keymap(mut self, keymap: KeyMap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Whether writing is refused because of safe mode, saying so in the
+    /// footer.
+    fn writes_blocked(&mut self) -> bool {
+        if self.safe_mode {
+            self.status_message = Some("Safe mode, nothing is written to disk".to_string());
         }
+        self.safe_mode
+    }
+
+    /// Shows a startup problem, e.g. an unreadable theme, in the footer until
+    /// the first key press.
+    pub fn warn(&mut self, message: String) {
+        self.status_message = Some(match self.status_message.take() {
+            Some(previous) => format!("{}. {}", previous, message),
+            None => message,
+        });
     }
 
     /// Run the application's main loop.
@@ -83,32 +362,122 @@ impl App {
         self.running = true;
 
         let tx = self.tx.clone();
+        let paused = self.events_paused.clone();
         std::thread::spawn(move || {
-            get_crossterm_events(tx.clone()).unwrap();
+            get_crossterm_events(tx.clone(), paused).unwrap();
         });
 
         while self.running {
             terminal.draw(|frame| self.draw(frame))?;
             self.handle_events()?;
+            self.autosave();
+            if std::mem::take(&mut self.edit_requested) {
+                self.events_paused.store(true, Ordering::SeqCst);
+                // Let a poll that's already underway run out
+                std::thread::sleep(EVENT_POLL_INTERVAL);
+                restore_terminal();
+                self.edit_data_file();
+                terminal = init_terminal(self.config.mouse.unwrap_or(true));
+                self.events_paused.store(false, Ordering::SeqCst);
+            }
         }
         Ok(())
     }
 
+    /// Saves, opens the data file in `$VISUAL` or `$EDITOR` and loads it back
+    /// once the editor exits. If the file no longer parses the todos are kept
+    /// as they were.
+    fn edit_data_file(&mut self) {
+        if self.writes_blocked() {
+            return;
+        }
+        if self.config.storage.unwrap_or_default() != Storage::Json {
+            self.status_message = Some(":edit needs the JSON storage".to_string());
+            return;
+        }
+        let path = match self.write_data_file() {
+            Ok(path) => path,
+            Err(err) => {
+                self.status_message = Some(format!("Could not save before editing: {}", err));
+                return;
+            }
+        };
+
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        // Allow editors that need flags, e.g. `code --wait`
+        let mut words = editor.split_whitespace();
+        let status = std::process::Command::new(words.next().unwrap_or("vi"))
+            .args(words)
+            .arg(&path)
+            .status();
+        if let Err(err) = status {
+            self.status_message = Some(format!("Could not run {}: {}", editor, err));
+            return;
+        }
+
+        match Store::from_json_file(&path) {
+            Ok(store) => self.load_store(store),
+            Err(err) => {
+                self.status_message = Some(format!(
+                    "Kept the todos as they were, {} is invalid: {}",
+                    path.display(),
+                    err
+                ))
+            }
+        }
+    }
+
+    /// Replaces everything with `store`. Keys into the old store, like the
+    /// clipboard and search results, are dropped.
+    fn load_store(&mut self, mut store: Store) {
+        self.status_message = duplicate_ids_message(store.reassign_duplicate_ids());
+        self.slot_map_store = SlotMapStore::from_store(&store);
+        self.slot_tree_state = SlotTreeState::new(&store, &self.slot_map_store, &self.config);
+        self.clipboard_todos.clear();
+        self.clipboard_workspaces.clear();
+        self.search_matches.clear();
+        self.search_workspace_hits = None;
+        self.current_match_index = 0;
+        self.last_opened_link = None;
+        self.last_completion = None;
+        self.moving_todos.clear();
+        self.workspace_accessed.clear();
+        self.confirmation = None;
+        self.sorting = SortingItem::None;
+        self.todo_offset = 0;
+    }
+
     pub fn get_store(&self) -> Store {
-        self.slot_map_store.get_store()
+        let mut store = self.slot_map_store.get_store();
+        let opened: HashSet<&str> = self
+            .slot_tree_state
+            .todo_opened
+            .iter()
+            .map(|key| self.slot_map_store.todos_map.get(*key).unwrap().id.as_str())
+            .collect();
+        for workspace in &mut store.workspaces {
+            workspace.record_opened_todos(&opened);
+        }
+        store
     }
 
-    pub fn sort_todos(&mut self, todos: &mut Vec<DefaultKey>, n: char) {
+    pub fn sort_todos(&mut self, todos: &mut [DefaultKey], n: char) {
         todos.sort_by(|a, b| {
             let a = self.slot_map_store.todos_map.get(*a).unwrap();
             let b = self.slot_map_store.todos_map.get(*b).unwrap();
 
-            match n {
+            // Pinned todos stay on top whatever the sort order
+            b.pinned.cmp(&a.pinned).then_with(|| match n {
                 '2' => a.description.cmp(&b.description),
-                '3' => a.pending.cmp(&b.pending),
+                '3' => b.status.cmp(&a.status),
                 '4' => a.urgency.cmp(&b.urgency),
+                // Pending first, completed sink to the bottom. `sort_by` is
+                // stable so the relative order within each group is kept.
+                '5' => a.status.is_done().cmp(&b.status.is_done()),
                 _ => a.description.cmp(&b.description),
-            }
+            })
         });
     }
 
@@ -124,33 +493,255 @@ impl App {
             Layout::horizontal(vec![Constraint::Percentage(20), Constraint::Fill(20)])
                 .areas(main_vertical_areas[0]);
 
-        self.slot_tree_state
-            .update_workspace_tree_state(&self.slot_map_store);
+        if self.slot_tree_state.dirty {
+            self.slot_tree_state
+                .update_workspace_tree_state(&self.slot_map_store);
+        }
 
         self.render_workspaces(frame, main_areas[0]);
         self.render_todos(frame, main_areas[1]);
         self.render_footer(frame, main_vertical_areas[1]);
+        if self.picker.is_some() {
+            self.render_picker(frame, main_areas[1]);
+        }
+        if self.show_help {
+            self.render_help(frame, main_vertical_areas[0]);
+        }
+    }
+
+    /// Draws one column per pane of `HELP`, centered over `area`.
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        let key_style = self
+            .theme
+            .paint(Style::new().fg(self.theme.highlight_text_secondary));
+        let columns: Vec<Vec<Line>> = HELP
+            .iter()
+            .map(|(pane, keys)| {
+                let key_width = keys.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+                let mut lines = vec![Line::from(*pane).bold()];
+                for (key, action) in keys.iter() {
+                    lines.push(Line::from(vec![
+                        Span::styled(format!("{:width$}  ", key, width = key_width), key_style),
+                        Span::raw(*action),
+                    ]));
+                }
+                lines
+            })
+            .collect();
+        let widths: Vec<u16> = columns
+            .iter()
+            .map(|lines| lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2)
+            .collect();
+
+        // Leave room for the block's borders and padding
+        let block = self.get_title_block(" Keys (? or Esc to close) ", true, None);
+        let probe = Rect::new(0, 0, 100, 100);
+        let inner = block.inner(probe);
+        let width = widths.iter().sum::<u16>() + probe.width - inner.width;
+        let height =
+            columns.iter().map(Vec::len).max().unwrap_or(0) as u16 + probe.height - inner.height;
+        let [_, area, _] = Layout::vertical(vec![
+            Constraint::Fill(1),
+            Constraint::Length(height),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
+        let [_, area, _] = Layout::horizontal(vec![
+            Constraint::Fill(1),
+            Constraint::Length(width),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
+
+        let column_areas =
+            Layout::horizontal(widths.into_iter().map(Constraint::Length)).split(block.inner(area));
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        for (lines, column_area) in columns.into_iter().zip(column_areas.iter()) {
+            frame.render_widget(List::new(lines), *column_area);
+        }
+    }
+
+    fn render_picker(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(picker) = &self.picker else {
+            return;
+        };
+
+        let [_, area, _] = Layout::vertical(vec![
+            Constraint::Percentage(20),
+            Constraint::Percentage(60),
+            Constraint::Percentage(20),
+        ])
+        .areas(area);
+        let [_, area, _] = Layout::horizontal(vec![
+            Constraint::Percentage(15),
+            Constraint::Percentage(70),
+            Constraint::Percentage(15),
+        ])
+        .areas(area);
+
+        let block = self.get_title_block(" Move under ", true, None);
+        let inner_area = block.inner(area);
+        let [query_area, list_area] =
+            Layout::vertical(vec![Constraint::Length(1), Constraint::Fill(1)]).areas(inner_area);
+
+        let items: Vec<ListItem> = self
+            .picker_candidates(picker)
+            .into_iter()
+            .map(|(key, depth)| {
+                let todo = self.slot_map_store.todos_map.get(key).unwrap();
+                ListItem::new(format!("{}{}", self.indent(depth), todo.description))
+            })
+            .collect();
+        let mut list_state = ListState::default();
+        if !items.is_empty() {
+            list_state.select(Some(picker.selected));
+        }
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        frame.render_widget(Line::from(format!("> {}", picker.query)), query_area);
+        frame.set_cursor_position(Position::new(
+            query_area.x + 2 + picker.query.chars().count() as u16,
+            query_area.y,
+        ));
+        frame.render_stateful_widget(
+            List::new(items).highlight_style(self.theme.paint(self.theme.selection())),
+            list_area,
+            &mut list_state,
+        );
     }
 
     fn render_footer(&mut self, frame: &mut Frame, area: Rect) {
         let mut line = Line::default();
-        if self.search_mode {
-            line.push_span(Span::raw(" Search: ").bg(Color::Blue).fg(self.theme.text));
+        if let Some(Confirmation::Replace { from, to, .. }) = &self.confirmation {
+            line.push_span(Span::styled(
+                " Replace ",
+                self.theme.label(Color::Black, Color::Yellow),
+            ));
+            line.push_span(Span::raw(format!(
+                " \"{}\" with \"{}\"?  y:Yes  n:No  a:All  q:Quit",
+                from, to
+            )));
+        } else if let Some(Confirmation::CutTodos | Confirmation::CutWorkspaces) =
+            &self.confirmation
+        {
+            let count = match self.confirmation {
+                Some(Confirmation::CutTodos) => self.slot_tree_state.multi_selected_todos.len(),
+                _ => self.slot_tree_state.multi_selected_workspaces.len(),
+            };
+            line.push_span(Span::styled(
+                " Confirm ",
+                self.theme.label(Color::Black, Color::Red),
+            ));
+            line.push_span(Span::raw(format!(" Cut {} items? (y/n)", count)));
+        } else if let Some(Confirmation::CutTodo(key) | Confirmation::CutWorkspace(key)) =
+            self.confirmation
+        {
+            let description = match self.confirmation {
+                Some(Confirmation::CutTodo(_)) => &self.slot_map_store.todos_map[key].description,
+                _ => &self.slot_map_store.workspaces_map[key].description,
+            };
+            line.push_span(Span::styled(
+                " Confirm ",
+                self.theme.label(Color::Black, Color::Red),
+            ));
+            line.push_span(Span::raw(format!(
+                " Cut '{}' and everything in it? (y/n)",
+                description
+            )));
+        } else if let Some((_, field)) = self.date_editing {
+            let label = match field {
+                DateField::Due => " Due (YYYY-MM-DD [HH:MM], tomorrow, +3d, mon) ",
+                DateField::Snooze => " Snooze until (YYYY-MM-DD [HH:MM], tomorrow, +3d, mon) ",
+            };
+            line.push_span(Span::styled(
+                label,
+                self.theme.label(self.theme.text, Color::Blue),
+            ));
+            line.push_span(Span::raw(format!(" {}", self.input.value())));
+            frame.set_cursor_position(Position::new(
+                area.x + (label.len() + 1 + self.input.visual_cursor()) as u16,
+                area.y,
+            ));
+        } else if self.tags_editing.is_some() {
+            let label = " Tags (separated by spaces) ";
+            line.push_span(Span::styled(
+                label,
+                self.theme.label(self.theme.text, Color::Blue),
+            ));
+            line.push_span(Span::raw(format!(" {}", self.input.value())));
+            frame.set_cursor_position(Position::new(
+                area.x + (label.len() + 1 + self.input.visual_cursor()) as u16,
+                area.y,
+            ));
+        } else if self.command_mode {
+            line.push_span(Span::styled(
+                " Command ",
+                self.theme.label(self.theme.text, Color::Blue),
+            ));
+            line.push_span(Span::raw(format!(" :{}", &self.command_str)));
+        } else if self.filter_mode {
+            line.push_span(Span::styled(
+                " Filter: ",
+                self.theme.label(self.theme.text, Color::Blue),
+            ));
+            line.push_span(Span::raw(format!(" {}", &self.filter_str)));
+        } else if self.search_mode {
+            let label = if self.search_global {
+                " Search all: "
+            } else {
+                " Search: "
+            };
+            line.push_span(Span::styled(
+                label,
+                self.theme.label(self.theme.text, Color::Blue),
+            ));
             line.push_span(Span::raw(format!(" {}", &self.search_str)));
         } else {
             match self.sorting {
                 SortingItem::None => {
-                    line.push_span(Span::raw(" INSERT ").bg(Color::Green).fg(Color::Black));
+                    line.push_span(Span::styled(
+                        " INSERT ",
+                        self.theme.label(Color::Black, Color::Green),
+                    ));
+                    if let Some(message) = &self.status_message {
+                        line.push_span(Span::raw(format!(" {}", message)));
+                    } else if !self.moving_todos.is_empty() {
+                        line.push_span(Span::raw(format!(
+                            " Moving {} todo{} - M on a workspace to move there, M in the todos to cancel",
+                            self.moving_todos.len(),
+                            if self.moving_todos.len() == 1 { "" } else { "s" }
+                        )));
+                    } else if let Some(completion) = &self.last_completion {
+                        let todo = self.slot_map_store.todos_map.get(completion.key).unwrap();
+                        line.push_span(Span::raw(format!(
+                            " Completed '{}' - u to undo",
+                            todo.description
+                        )));
+                    } else if !self.filter_str.is_empty() {
+                        line.push_span(Span::raw(format!(
+                            " Filtered by '{}' - C-f then Esc to clear",
+                            self.filter_str
+                        )));
+                    }
                 }
                 SortingItem::Todo(_) => {
-                    line.push_span(Span::raw(" Sort by: ").bg(Color::Blue).fg(self.theme.text));
+                    line.push_span(Span::styled(
+                        " Sort by: ",
+                        self.theme.label(self.theme.text, Color::Blue),
+                    ));
                     line.push_span(Span::raw(" 1:Reverse "));
                     line.push_span(Span::raw(" 2:Description "));
                     line.push_span(Span::raw(" 3:Pending "));
                     line.push_span(Span::raw(" 4:Urgency "));
+                    line.push_span(Span::raw(" 5:Completion "));
                 }
                 SortingItem::Workspace(_) => {
-                    line.push_span(Span::raw(" Sort by: ").bg(Color::Cyan).fg(Color::Black));
+                    line.push_span(Span::styled(
+                        " Sort by: ",
+                        self.theme.label(Color::Black, Color::Cyan),
+                    ));
                     line.push_span(Span::raw(" 1:Reverse "));
                     line.push_span(Span::raw(" 2:Description "));
                 }
@@ -158,31 +749,106 @@ impl App {
         }
 
         frame.render_widget(line, area);
+
+        let now = SystemTime::now();
+        let overdue = self
+            .slot_tree_state
+            .pending_due
+            .iter()
+            .filter(|due| dates::is_overdue(**due, now))
+            .count();
+        if overdue > 0 {
+            frame.render_widget(
+                Line::from(format!("\u{26a0} {} overdue ", overdue))
+                    .style(self.theme.paint(Style::new().fg(self.theme.overdue)))
+                    .right_aligned(),
+                area,
+            );
+        }
     }
 
     fn render_workspaces(&mut self, frame: &mut Frame, area: Rect) {
         let mut items: Vec<ListItem> = Vec::new();
+        let ancestors = self.selected_workspace_ancestors();
+        let block = self.get_title_block(
+            " Workspaces ",
+            self.active_screen == Screen::Workspaces,
+            None,
+        );
+        let list_area = block.inner(area);
 
         self.slot_tree_state.ws_tree.iter().for_each(|w| {
             let workspace = self.slot_map_store.workspaces_map.get(w.key).unwrap();
-            let mut item = ListItem::new(format!(
-                "{}{}{}",
-                "  ".repeat(w.depth),
+            let TodoCounts { done, total, .. } = self.slot_tree_state.subtree_counts[&w.key];
+            let name = format!(
+                "{}{}{}{}",
+                self.tree_prefix(w),
                 workspace.description.clone(),
                 if workspace.children.is_empty() || self.slot_tree_state.ws_opened.contains(&w.key)
                 {
                     "".to_string()
                 } else {
                     format!("({})", workspace.children.len())
+                },
+                // Pending todos in the whole subtree
+                if done < total {
+                    format!(" [{}]", total - done)
+                } else {
+                    "".to_string()
                 }
-            ));
+            );
+            let mut line = Line::from(name.clone());
+            // Right aligned progress of the whole subtree. The percentage and
+            // then the bar are left out when the name would run into them
+            if let Some(percent) = (done * 100).checked_div(total) {
+                let filled = done * PROGRESS_WIDTH / total;
+                let mut progress = format!(" {:>3}%", percent);
+                let available = list_area.width as usize;
+                let mut width = name.chars().count() + 1 + PROGRESS_WIDTH + progress.len();
+                if width > available {
+                    width -= progress.len();
+                    progress.clear();
+                }
+                if let Some(gap) = available.checked_sub(width) {
+                    line.push_span(Span::raw(" ".repeat(gap + 1)));
+                    line.push_span(Span::styled(
+                        "━".repeat(filled),
+                        self.theme.paint(Style::new().fg(Color::Green)),
+                    ));
+                    line.push_span(Span::styled(
+                        "─".repeat(PROGRESS_WIDTH - filled),
+                        self.theme.paint(Style::new().fg(self.theme.text_completed)),
+                    ));
+                    line.push_span(Span::raw(progress));
+                }
+            }
+            let mut item = ListItem::new(line);
 
             let mut item_style = Style::default();
             if let Some(selected) = self.slot_tree_state.selected_workspace {
                 if selected == w.key {
-                    item_style = item_style.fg(self.theme.text).bg(self.theme.item_highlight);
+                    item_style = self.theme.selection();
+                }
+            }
+
+            // Show which branch the selected workspace is in
+            if ancestors.contains(&w.key) {
+                item_style = item_style.bold();
+            }
+
+            if let Some(accent) = workspace_accent(workspace) {
+                item_style = item_style.fg(accent);
+            }
+
+            // Dim workspaces without matches during a global search
+            if let Some(hits) = &self.search_workspace_hits {
+                if !hits.contains(&w.key) {
+                    item_style = item_style.fg(self.theme.text_completed);
                 }
             }
+            if self.search_matches.contains(&SearchMatch::Workspace(w.key)) {
+                item_style = item_style.fg(Color::Yellow).bold();
+            }
 
             // Highlight multi-selected items
             if self
@@ -190,15 +856,13 @@ impl App {
                 .multi_selected_workspaces
                 .contains(&w.key)
             {
-                item_style = item_style.fg(Color::Yellow);
+                item_style = item_style.patch(self.theme.marked());
             }
 
-            item = item.style(item_style);
+            item = item.style(self.theme.paint(item_style));
             items.push(item);
         });
 
-        let block = self.get_title_block(" Workspaces ", self.active_screen == Screen::Workspaces);
-
         // Render the input
         if let Some(editing_id) = self.new_editing_id {
             let cursor_x = self.input.visual_cursor();
@@ -212,25 +876,24 @@ impl App {
             if let Some(ind) = ind {
                 let mut list_item = ListItem::new(format!(
                     "{}{}",
-                    "  ".repeat(self.slot_tree_state.ws_tree[ind].depth),
+                    self.tree_prefix(&self.slot_tree_state.ws_tree[ind]),
                     self.input.value()
                 ));
 
                 // TODO: Refactor these out
                 if let Some(selected) = self.slot_tree_state.selected_workspace {
                     if selected == editing_id {
-                        list_item = list_item.style(
-                            Style::default()
-                                .fg(self.theme.text)
-                                .bg(self.theme.item_highlight),
-                        );
+                        list_item = list_item.style(self.theme.selection());
                     }
                 }
 
                 items[ind] = list_item;
 
                 let y = ind;
-                let x = self.slot_tree_state.ws_tree[ind].depth * 2;
+                let x = self
+                    .tree_prefix(&self.slot_tree_state.ws_tree[ind])
+                    .chars()
+                    .count();
                 let inner_area = block.inner(area);
                 frame.set_cursor_position(Position::new(
                     inner_area.x + (x + cursor_x) as u16,
@@ -241,7 +904,9 @@ impl App {
 
         let widget = List::new(items).block(block);
 
-        let mut list_state = ListState::default();
+        // Starting from the last offset the list only scrolls once the
+        // selection would leave the pane
+        let mut list_state = ListState::default().with_offset(self.workspaces_area.offset);
         if let Some(selected_workspace) = self.slot_tree_state.selected_workspace {
             let index = self
                 .slot_tree_state
@@ -253,97 +918,333 @@ impl App {
         }
 
         frame.render_stateful_widget(widget, area, &mut list_state);
+        self.workspaces_area = PaneArea {
+            pane: area,
+            list: list_area,
+            offset: list_state.offset(),
+        };
+        self.render_scrollbar(
+            frame,
+            self.workspaces_area,
+            self.slot_tree_state.ws_tree.len(),
+        );
+    }
+
+    /// Draws a scrollbar on the right edge of a pane listing `len` items, as
+    /// long as they don't all fit.
+    fn render_scrollbar(&self, frame: &mut Frame, area: PaneArea, len: usize) {
+        let height = area.list.height as usize;
+        if len <= height {
+            return;
+        }
+        // Level with the items, over the border unless the layout is compact
+        let bar_area = Rect {
+            y: area.list.y,
+            height: area.list.height,
+            ..area.pane
+        };
+        let mut state = ScrollbarState::new(len - height + 1)
+            .viewport_content_length(height)
+            .position(area.offset);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .style(
+                    self.theme
+                        .paint(Style::new().fg(self.theme.highlight_text_secondary)),
+                ),
+            bar_area,
+            &mut state,
+        );
     }
 
-    fn get_title_block(&self, title: &str, active: bool) -> Block {
+    fn get_title_block(&self, title: &str, active: bool, accent: Option<Color>) -> Block<'static> {
         let styles = if active {
+            let highlight = accent.unwrap_or(self.theme.active_highlight);
             (
-                Style::default()
-                    .fg(self.theme.text_dark)
-                    .bg(self.theme.active_highlight),
-                Style::default().fg(self.theme.active_highlight),
+                self.theme.label(self.theme.text_dark, highlight),
+                Style::default().fg(highlight),
             )
         } else {
             (
                 Style::default()
                     .fg(self.theme.highlight_text_secondary)
                     .bg(self.theme.inactive_highlight),
-                Style::default().fg(self.theme.inactive_highlight),
+                Style::default().fg(accent.unwrap_or(self.theme.inactive_highlight)),
             )
         };
 
+        // Everything placed inside goes by `Block::inner`, so dropping the
+        // borders and padding needs no changes elsewhere.
+        if self.compact {
+            return Block::new()
+                .title(title.to_string())
+                .title_style(self.theme.paint(styles.0));
+        }
+
         let block = Block::bordered()
             .title(title.to_string())
-            .title_style(styles.0)
-            .border_style(styles.1)
+            .title_style(self.theme.paint(styles.0))
+            .border_style(self.theme.paint(styles.1))
             .padding(Padding::uniform(1));
 
-        return block;
+        block
     }
 
-    fn render_todos(&mut self, frame: &mut Frame, area: Rect) {
-        let mut rows: Vec<Row> = Vec::new();
-        self.slot_tree_state.todo_tree.iter().for_each(|t| {
-            let todo = self.slot_map_store.todos_map.get(t.key).unwrap();
+    /// Progress of the selected workspace's todos, nested ones included, by
+    /// count and, when any effort is set, weighted by effort.
+    fn workspace_summary(&self) -> Option<String> {
+        let TodoCounts {
+            done,
+            total,
+            done_effort,
+            total_effort,
+        } = *self
+            .slot_tree_state
+            .own_counts
+            .get(&self.slot_tree_state.selected_workspace?)?;
+        if total == 0 {
+            return None;
+        }
 
-            let icon = if todo.pending { " " } else { " " };
-            let mut todo_desc: Span = todo.description.clone().into();
-            let mut pre_desc = Span::from(format!("{}{} ", "  ".repeat(t.depth), icon))
-                .style(Style::new().fg(Color::Yellow));
+        let mut summary = format!(" {}/{} done", done, total);
+        if let Some(percent) = (done_effort * 100).checked_div(total_effort) {
+            summary.push_str(&format!(
+                ", {}% of effort ({}/{})",
+                percent, done_effort, total_effort
+            ));
+        }
+        summary.push(' ');
+        Some(summary)
+    }
 
-            if !todo.pending {
-                todo_desc =
-                    todo_desc.style(Style::new().fg(self.theme.text_completed).crossed_out());
-                pre_desc = pre_desc.style(Style::new().fg(Color::Green));
-            }
+    /// Descriptions from the outermost ancestor down to the focused todo.
+    fn focus_breadcrumb(&self) -> Option<String> {
+        let focused = self.slot_tree_state.focused_todo?;
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(self.slot_tree_state.selected_workspace?)?;
+        let mut path = find_todo_path(&self.slot_map_store.todos_map, &workspace.todos, focused)?;
+        path.push(focused);
+        Some(
+            path.iter()
+                .map(|key| {
+                    self.slot_map_store
+                        .todos_map
+                        .get(*key)
+                        .unwrap()
+                        .description
+                        .as_str()
+                })
+                .collect::<Vec<_>>()
+                .join(" \u{203a} "),
+        )
+    }
 
-            if self.search_matches.contains(&t.key) {
-                todo_desc = todo_desc.style(Style::new().fg(Color::Yellow).bold());
+    fn render_todos(&mut self, frame: &mut Frame, area: Rect) {
+        let mut todos_title = match self.focus_breadcrumb() {
+            Some(breadcrumb) => format!(" Todos \u{203a} {} (F to zoom out) ", breadcrumb),
+            None => " Todos ".to_string(),
+        };
+        if !self.filter_str.is_empty() {
+            todos_title.push_str(&format!("(filter: {}) ", self.filter_str));
+        }
+        if self.slot_tree_state.hide_completed {
+            todos_title.push_str("(done hidden) ");
+        }
+        let remaining_effort = self.slot_tree_state.remaining_effort;
+        if remaining_effort > 0 {
+            todos_title.push_str(&format!("({} effort left) ", remaining_effort));
+        }
+
+        let accent = self
+            .slot_tree_state
+            .selected_workspace
+            .and_then(|key| workspace_accent(self.slot_map_store.workspaces_map.get(key).unwrap()));
+        let mut block = self.get_title_block(
+            todos_title.as_str(),
+            self.active_screen == Screen::Todos,
+            accent,
+        );
+        if let Some(summary) = self.workspace_summary() {
+            block = block.title_bottom(Line::from(summary).right_aligned());
+        }
+
+        // Only build rows for the todos that fit in the pane. The offset
+        // follows the selection so it always stays in view.
+        let list_area = block.inner(area);
+        let height = list_area.height as usize;
+        let selected_index = self.slot_tree_state.selected_todo.map(|selected_todo| {
+            self.slot_tree_state
+                .todo_tree
+                .iter()
+                .position(|w| w.key == selected_todo)
+                .unwrap()
+        });
+        if let Some(index) = selected_index {
+            if index < self.todo_offset {
+                self.todo_offset = index;
+            } else if index >= self.todo_offset + height {
+                self.todo_offset = index + 1 - height;
             }
+        }
+        self.todo_offset = self
+            .todo_offset
+            .min(self.slot_tree_state.todo_tree.len().saturating_sub(height));
+        let start = self.todo_offset;
+        let end = (start + height).min(self.slot_tree_state.todo_tree.len());
+        self.todos_area = PaneArea {
+            pane: area,
+            list: list_area,
+            offset: start,
+        };
 
-            let mut todo_line = Line::from(pre_desc);
-            todo_line.push_span(todo_desc);
+        let now = SystemTime::now();
+        // The due date column is only there while some todo in the pane has
+        // one, sized to the longest so it doesn't shift while scrolling
+        let due_width = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .filter_map(|t| self.slot_map_store.todos_map.get(t.key).unwrap().due)
+            .map(|due| dates::format_due(due).len())
+            .max();
+        // Likewise for effort, hidden while every todo's is 0
+        let effort_width = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .map(|t| self.slot_map_store.todos_map.get(t.key).unwrap().effort)
+            .filter(|effort| *effort > 0)
+            .map(|effort| effort.to_string().len())
+            .max();
+        let mut rows: Vec<Row> = Vec::new();
+        self.slot_tree_state.todo_tree[start..end]
+            .iter()
+            .for_each(|t| {
+                let todo = self.slot_map_store.todos_map.get(t.key).unwrap();
 
-            // show children count
-            if !todo.children.is_empty() {
-                let mut done_count = 0;
-                todo.children.iter().for_each(|child_key| {
-                    let todo = self.slot_map_store.todos_map.get(*child_key).unwrap();
-                    if !todo.pending {
-                        done_count += 1;
-                    }
-                });
+                let done_count = todo
+                    .children
+                    .iter()
+                    .filter(|child_key| {
+                        let child = self.slot_map_store.todos_map.get(**child_key).unwrap();
+                        child.status == Status::Done
+                    })
+                    .count();
+                let partially_done = done_count > 0 && done_count < todo.children.len();
+
+                let (icon, icon_color) = match todo.status {
+                    Status::Todo if partially_done => ("\u{25d0} ", Color::LightYellow),
+                    Status::Todo => (" ", Color::Yellow),
+                    Status::Doing => (" ", Color::Cyan),
+                    Status::Done => (" ", Color::Green),
+                };
+                let mut todo_desc: Span = todo.description.clone().into();
+                let pre_desc = Span::from(format!("{}{} ", self.tree_prefix(t), icon))
+                    .style(self.theme.paint(Style::new().fg(icon_color)));
+
+                let overdue = todo.due.is_some_and(|due| dates::is_overdue(due, now));
+                if todo.status == Status::Done {
+                    todo_desc = todo_desc.style(
+                        self.theme
+                            .paint(Style::new().fg(self.theme.text_completed).crossed_out()),
+                    );
+                } else if overdue {
+                    todo_desc =
+                        todo_desc.style(self.theme.paint(Style::new().fg(self.theme.overdue)));
+                }
 
-                todo_line.push_span(Span::styled(
-                    format!(" {}{}/{}", icon, done_count, todo.children.len()),
-                    Style::default().fg(Color::LightGreen),
-                ));
-            }
+                if self
+                    .search_matches
+                    .iter()
+                    .any(|m| matches!(m, SearchMatch::Todo { todo, .. } if *todo == t.key))
+                {
+                    todo_desc =
+                        todo_desc.style(self.theme.paint(Style::new().fg(Color::Yellow).bold()));
+                }
 
-            let mut priority = Line::from(PRIORITIES[todo.urgency as usize]);
-            priority = priority.style(Style::new().fg(PRIORITY_COLORS[todo.urgency as usize]));
+                let mut todo_line = Line::from(pre_desc);
+                let due = todo.due.map(|due| {
+                    let color = if !todo.status.is_done() && overdue {
+                        self.theme.overdue
+                    } else {
+                        self.theme.highlight_text_secondary
+                    };
+                    Span::styled(
+                        dates::format_due(due),
+                        self.theme.paint(Style::new().fg(color)),
+                    )
+                });
+                if todo.pinned {
+                    todo_line.push_span(Span::styled(
+                        "\u{f0403} ",
+                        self.theme.paint(Style::new().fg(Color::Red)),
+                    ));
+                }
+                todo_line.push_span(todo_desc);
+                for tag in &todo.tags {
+                    todo_line.push_span(Span::styled(
+                        format!(" #{}", tag),
+                        self.theme.paint(Style::new().fg(tag_color(tag))),
+                    ));
+                }
+                // Only listed while `Z` shows snoozed todos
+                if let Some(until) = todo.snooze_until.filter(|until| *until > now) {
+                    todo_line.push_span(Span::styled(
+                        format!(" \u{f04b2} {}", dates::format_due(until)),
+                        self.theme.paint(Style::new().fg(self.theme.text_completed)),
+                    ));
+                }
 
-            let mut row_style = Style::default();
-            let mut row = Row::new(vec![todo_line, priority.into()]);
-            if let Some(selected) = self.slot_tree_state.selected_todo {
-                if selected == t.key {
-                    row_style = row_style.bg(self.theme.item_highlight);
+                // show children count
+                if self.show_child_counts && !todo.children.is_empty() {
+                    todo_line.push_span(Span::styled(
+                        format!(" {}{}/{}", icon, done_count, todo.children.len()),
+                        self.theme.paint(Style::default().fg(Color::LightGreen)),
+                    ));
                 }
-            }
 
-            // Highlight multi-selected items
-            if self.slot_tree_state.multi_selected_todos.contains(&t.key) {
-                row_style = row_style.fg(Color::Yellow);
-            }
+                let todo_priority = self.theme.priority(todo.urgency);
+                let mut priority = Line::from(todo_priority.glyph.clone());
+                priority = priority.style(self.theme.paint(Style::new().fg(todo_priority.color)));
 
-            row = row.style(row_style);
+                let mut row_style = Style::default();
+                let mut cells = vec![todo_line];
+                if due_width.is_some() {
+                    cells.push(due.map(Line::from).unwrap_or_default());
+                }
+                if effort_width.is_some() {
+                    cells.push(if todo.effort > 0 {
+                        Line::from(todo.effort.to_string())
+                            .style(
+                                self.theme
+                                    .paint(Style::new().fg(self.theme.inactive_highlight)),
+                            )
+                            .right_aligned()
+                    } else {
+                        Line::default()
+                    });
+                }
+                cells.push(priority);
+                let mut row = Row::new(cells);
+                if let Some(selected) = self.slot_tree_state.selected_todo {
+                    if selected == t.key {
+                        row_style = row_style.patch(self.theme.selection());
+                    }
+                }
 
-            rows.push(row);
-        });
+                // Highlight multi-selected items
+                if self.slot_tree_state.multi_selected_todos.contains(&t.key) {
+                    row_style = row_style.patch(self.theme.marked());
+                }
 
-        let todos_title = " Todos ".to_string();
+                row = row.style(self.theme.paint(row_style));
 
-        let block = self.get_title_block(todos_title.as_str(), self.active_screen == Screen::Todos);
+                rows.push(row);
+            });
 
         // Render the input
         if let Some(editing_id) = self.new_editing_id {
@@ -355,23 +1256,22 @@ impl App {
                 .iter()
                 .position(|w| w.key == editing_id);
 
-            if let Some(ind) = ind {
+            if let Some(ind) = ind.filter(|ind| (start..end).contains(ind)) {
                 let todo_desc = format!(
                     "{}{} {}",
-                    "  ".repeat(self.slot_tree_state.todo_tree[ind].depth),
+                    self.tree_prefix(&self.slot_tree_state.todo_tree[ind]),
                     " ",
                     self.input.value()
                 );
                 let mut row = Row::new(vec![todo_desc]);
-                row = row.style(
-                    Style::default()
-                        .fg(self.theme.text)
-                        .bg(self.theme.item_highlight),
-                );
-                rows[ind] = row;
-
-                let y = ind;
-                let x = self.slot_tree_state.todo_tree[ind].depth * 2;
+                row = row.style(self.theme.selection());
+                rows[ind - start] = row;
+
+                let y = ind - start;
+                let x = self
+                    .tree_prefix(&self.slot_tree_state.todo_tree[ind])
+                    .chars()
+                    .count();
                 let inner_area = block.inner(area);
                 frame.set_cursor_position(Position::new(
                     inner_area.x + (x + cursor_x) as u16 + 3,
@@ -380,165 +1280,386 @@ impl App {
             }
         }
 
-        let widths = [Constraint::Fill(5), Constraint::Length(2)];
+        let mut widths = vec![Constraint::Fill(5)];
+        if let Some(due_width) = due_width {
+            widths.push(Constraint::Length(due_width as u16));
+        }
+        if let Some(effort_width) = effort_width {
+            widths.push(Constraint::Length(effort_width as u16));
+        }
+        widths.push(Constraint::Length(2));
 
         let widget = Table::new(rows, widths).block(block);
 
         let mut table_state = TableState::default();
-        if let Some(selected_todo) = self.slot_tree_state.selected_todo {
-            let index = self
-                .slot_tree_state
-                .todo_tree
-                .iter()
-                .position(|w| w.key == selected_todo)
-                .unwrap();
-            table_state.select(Some(index));
-        }
+        table_state.select(
+            selected_index
+                .filter(|index| (start..end).contains(index))
+                .map(|index| index - start),
+        );
 
         frame.render_stateful_widget(widget, area, &mut table_state);
+        self.render_scrollbar(frame, self.todos_area, self.slot_tree_state.todo_tree.len());
     }
 
     fn handle_events(&mut self) -> Result<()> {
-        let event = self.rx.recv()?;
-        if let Event::Key(key) = event {
-            if key.kind == KeyEventKind::Press {
-                self.handle_crossterm_events(event)?
+        let event = match self.rx.recv_timeout(self.tick_rate()) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.on_tick();
+                return Ok(());
             }
+            Err(err) => return Err(err.into()),
+        };
+        self.handle_crossterm_events(event)
+    }
+
+    /// Leading whitespace for an item `depth` levels deep.
+    fn indent(&self, depth: usize) -> String {
+        " ".repeat(self.indent_width() * depth)
+    }
+
+    fn indent_width(&self) -> usize {
+        self.config.indent_width.unwrap_or(2).clamp(1, 4)
+    }
+
+    /// What's drawn in front of a tree item, either plain indentation or tree
+    /// guides. Both the rendering and the cursor placement while editing go
+    /// through this so they line up.
+    fn tree_prefix(&self, item: &ActiveTree) -> String {
+        if !self.config.tree_guides.unwrap_or(false) {
+            // `last_child` has an entry per level drawn, which in focus mode
+            // starts at the focused todo rather than the workspace
+            return self.indent(item.last_child.len() - 1);
         }
-        Ok(())
+
+        let width = self.indent_width();
+        let mut prefix = String::new();
+        // Top level items have no guides, so skip their level
+        for (level, is_last) in item.last_child.iter().enumerate().skip(1) {
+            let own_level = level + 1 == item.last_child.len();
+            let (first, fill) = match (own_level, is_last) {
+                (true, false) => ("├", "─"),
+                (true, true) => ("└", "─"),
+                (false, false) => ("│", " "),
+                (false, true) => (" ", " "),
+            };
+            prefix.push_str(first);
+            prefix.push_str(&fill.repeat(width - 1));
+        }
+        prefix
     }
 
-    fn update_search_matches(&mut self) {
-        self.search_matches.clear();
-        self.current_match_index = 0;
+    fn tick_rate(&self) -> Duration {
+        self.config
+            .tick_rate_ms
+            .map(|ms| Duration::from_millis(ms.max(1)))
+            .unwrap_or(DEFAULT_TICK_RATE)
+    }
 
-        if self.search_str.is_empty() {
+    /// Runs when no event arrived for a tick. The screen is redrawn
+    /// afterwards, so time based state put here stays current.
+    fn on_tick(&mut self) {
+        if let Some(secs) = self.config.collapse_idle_workspaces_secs {
+            self.collapse_idle_workspaces(Duration::from_secs(secs));
+        }
+        if self
+            .slot_tree_state
+            .next_unsnooze
+            .is_some_and(|until| until <= SystemTime::now())
+        {
+            self.slot_tree_state.invalidate();
+        }
+        if self
+            .last_completion
+            .as_ref()
+            .is_some_and(|completion| completion.at.elapsed() >= UNDO_COMPLETION_TIMEOUT)
+        {
+            self.last_completion = None;
+        }
+    }
+
+    /// Marks the selected workspace and its ancestors as just used, for
+    /// `collapse_idle_workspaces_secs`.
+    fn touch_selected_workspace(&mut self) {
+        if self.config.collapse_idle_workspaces_secs.is_none() {
             return;
         }
+        let now = Instant::now();
+        let mut path = self.selected_workspace_ancestors();
+        path.extend(self.slot_tree_state.selected_workspace);
+        for key in path {
+            self.workspace_accessed.insert(key, now);
+        }
+    }
 
-        if let Some(workspace_key) = self.slot_tree_state.selected_workspace {
-            let workspace = self
-                .slot_map_store
-                .workspaces_map
-                .get(workspace_key)
-                .unwrap();
+    /// Collapses expanded workspaces that haven't been on the selection's path
+    /// for `idle`. Workspaces expanded without being selected count from the
+    /// first tick that sees them.
+    fn collapse_idle_workspaces(&mut self, idle: Duration) {
+        let now = Instant::now();
+        let mut path = self.selected_workspace_ancestors();
+        path.extend(self.slot_tree_state.selected_workspace);
+
+        let accessed = &mut self.workspace_accessed;
+        let opened = &mut self.slot_tree_state.ws_opened;
+        let count = opened.len();
+        opened.retain(|key| {
+            path.contains(key) || now.duration_since(*accessed.entry(*key).or_insert(now)) < idle
+        });
+        accessed.retain(|key, _| opened.contains(key));
+        if opened.len() != count {
+            self.slot_tree_state.invalidate();
+        }
+    }
 
-            // Helper function to recursively search todos
-            fn search_todos(
-                todos_map: &SlotMap<DefaultKey, TodoItem>,
-                todo_key: DefaultKey,
-                search_str: &str,
-                matches: &mut Vec<DefaultKey>,
-                todos_containing_matches: &mut Vec<DefaultKey>,
-            ) -> bool {
-                let todo = todos_map.get(todo_key).unwrap();
-
-                let mut contains_match = false;
-
-                if todo
-                    .description
-                    .to_lowercase()
-                    .contains(&search_str.to_lowercase())
-                {
-                    matches.push(todo_key);
-                    contains_match = true;
-                }
-
-                // Search in children
-                for child_key in &todo.children {
-                    if search_todos(
-                        todos_map,
-                        *child_key,
-                        search_str,
-                        matches,
-                        todos_containing_matches,
-                    ) {
-                        contains_match = true;
-                    }
-                }
+    /// Narrows the todos pane down to the todos matching `filter_str`, read
+    /// the same way as a search.
+    fn update_filter(&mut self) {
+        self.slot_tree_state.filter = (!self.filter_str.is_empty())
+            .then(|| Query::parse(&self.filter_str, self.theme.max_urgency()));
+        self.slot_tree_state.invalidate();
+    }
 
-                if contains_match {
-                    todos_containing_matches.push(todo_key);
-                }
+    fn update_search_matches(&mut self) {
+        self.slot_tree_state.invalidate();
+        self.search_matches.clear();
+        self.search_workspace_hits = None;
+        self.current_match_index = 0;
 
-                return contains_match;
-            }
+        if self.search_str.is_empty() {
+            return;
+        }
 
-            // Search in workspace's direct todos
-            let mut todos_containing_matches: Vec<DefaultKey> = Vec::new();
-            for todo_key in &workspace.todos {
-                search_todos(
-                    &self.slot_map_store.todos_map,
-                    *todo_key,
-                    &self.search_str,
-                    &mut self.search_matches,
-                    &mut todos_containing_matches,
-                );
+        let mut todos_containing_matches: Vec<DefaultKey> = Vec::new();
+        if self.search_global {
+            let mut hits = HashSet::new();
+            for workspace_key in self.slot_map_store.root_workspaces.clone() {
+                self.search_workspace_tree(workspace_key, &mut todos_containing_matches, &mut hits);
             }
+            self.search_workspace_hits = Some(hits);
+        } else if let Some(workspace_key) = self.slot_tree_state.selected_workspace {
+            self.search_workspace_todos(workspace_key, &mut todos_containing_matches);
+        } else {
+            return;
+        }
 
-            self.slot_tree_state.todo_opened.clear();
-            for todo_key in &todos_containing_matches {
-                self.slot_tree_state.todo_opened.insert(*todo_key);
-            }
+        self.slot_tree_state.todo_opened.clear();
+        for todo_key in &todos_containing_matches {
+            self.slot_tree_state.todo_opened.insert(*todo_key);
         }
     }
 
-    fn clone_todo(&mut self, todo_key: DefaultKey) -> DefaultKey {
-        let old_todo = self.slot_map_store.todos_map.get(todo_key).unwrap().clone();
-
-        let mut todo = TodoItem {
-            id: Uuid::new_v4().to_string(),
-            description: old_todo.description.clone(),
-            pending: old_todo.pending,
-            urgency: old_todo.urgency,
-            effort: old_todo.effort,
-            due: old_todo.due,
-            children: Vec::new(),
-        };
+    /// Searches the todos of a single workspace, returning whether any matched.
+    fn search_workspace_todos(
+        &mut self,
+        workspace_key: DefaultKey,
+        todos_containing_matches: &mut Vec<DefaultKey>,
+    ) -> bool {
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(workspace_key)
+            .unwrap();
 
-        for todo_key in old_todo.children.iter() {
-            let key = self.clone_todo(*todo_key);
-            todo.children.push(key);
+        let query = Query::parse(&self.search_str, self.theme.max_urgency());
+        let now = SystemTime::now();
+        let mut matches = Vec::new();
+        for todo_key in &workspace.todos {
+            search_todos(
+                &self.slot_map_store.todos_map,
+                *todo_key,
+                &query,
+                now,
+                &mut matches,
+                todos_containing_matches,
+            );
         }
 
-        return self.slot_map_store.todos_map.insert(todo);
+        self.search_matches
+            .extend(matches.iter().map(|todo| SearchMatch::Todo {
+                workspace: workspace_key,
+                todo: *todo,
+            }));
+
+        !matches.is_empty()
     }
 
-    fn clone_workspace(&mut self, workspace_key: DefaultKey) -> DefaultKey {
-        let old_workspace = self
+    /// Searches a workspace's name, its todos and its descendants, recording
+    /// every workspace whose subtree has a match in `hits` and expanding the
+    /// ancestors of matching workspaces.
+    fn search_workspace_tree(
+        &mut self,
+        workspace_key: DefaultKey,
+        todos_containing_matches: &mut Vec<DefaultKey>,
+        hits: &mut HashSet<DefaultKey>,
+    ) -> bool {
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(workspace_key)
+            .unwrap();
+        let query = Query::parse(&self.search_str, self.theme.max_urgency());
+        let mut contains_match = query.matches_name(&workspace.description);
+        if contains_match {
+            self.search_matches
+                .push(SearchMatch::Workspace(workspace_key));
+        }
+
+        if self.search_workspace_todos(workspace_key, todos_containing_matches) {
+            contains_match = true;
+        }
+
+        let children = self
             .slot_map_store
             .workspaces_map
             .get(workspace_key)
             .unwrap()
+            .children
             .clone();
+        for child_key in children {
+            if self.search_workspace_tree(child_key, todos_containing_matches, hits) {
+                self.slot_tree_state.ws_opened.insert(workspace_key);
+                contains_match = true;
+            }
+        }
+
+        if contains_match {
+            hits.insert(workspace_key);
+        }
 
-        let mut workspace = WorkspaceItem {
-            id: Uuid::new_v4().to_string(),
-            description: old_workspace.description.clone(),
+        contains_match
+    }
+
+    fn clone_todo(&mut self, todo_key: DefaultKey) -> DefaultKey {
+        // Walk with an explicit stack, like the tree builders, so very deep
+        // todos can't overflow the call stack
+        let todos_map = &mut self.slot_map_store.todos_map;
+        let copy = |todo: &TodoItem| TodoItem {
+            id: store::new_id(),
             children: Vec::new(),
-            todos: Vec::new(),
+            ..todo.clone()
         };
-
-        for child_key in old_workspace.children.iter() {
-            let key = self.clone_workspace(*child_key);
-            workspace.children.push(key);
+        let root = todos_map.insert(copy(&todos_map[todo_key]));
+        let mut stack: Vec<(DefaultKey, DefaultKey)> = todos_map[todo_key]
+            .children
+            .iter()
+            .rev()
+            .map(|child| (*child, root))
+            .collect();
+        while let Some((old_key, parent)) = stack.pop() {
+            let key = todos_map.insert(copy(&todos_map[old_key]));
+            todos_map[parent].children.push(key);
+            stack.extend(
+                todos_map[old_key]
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|child| (*child, key)),
+            );
         }
+        root
+    }
+
+    /// Inserts `new_key` right after the selected workspace, at the same
+    /// level, or at the end of the top level when nothing is selected.
+    fn insert_workspace_after_selected(&mut self, new_key: DefaultKey) {
+        if let Some(selected) = self.slot_tree_state.selected_workspace {
+            // Find from rendered.
+            let parent_key = self
+                .slot_tree_state
+                .ws_tree
+                .iter()
+                .find(|w| w.key == selected)
+                .unwrap()
+                .parent;
 
-        for todo_key in old_workspace.todos.iter() {
-            let key = self.clone_todo(*todo_key);
-            workspace.todos.push(key);
+            if let Some(parent_key) = parent_key {
+                // Nested
+                let workspace = self
+                    .slot_map_store
+                    .workspaces_map
+                    .get_mut(parent_key)
+                    .unwrap();
+                let ind = workspace
+                    .children
+                    .iter()
+                    .position(|k| *k == selected)
+                    .unwrap();
+                workspace.children.insert(ind + 1, new_key);
+            } else {
+                // Top level
+                let ind = self
+                    .slot_map_store
+                    .root_workspaces
+                    .iter()
+                    .position(|k| *k == selected)
+                    .unwrap();
+                self.slot_map_store.root_workspaces.insert(ind + 1, new_key);
+            }
+        } else {
+            self.slot_map_store.root_workspaces.push(new_key);
         }
+    }
 
-        return self.slot_map_store.workspaces_map.insert(workspace);
+    fn clone_workspace(&mut self, workspace_key: DefaultKey) -> DefaultKey {
+        let copy = |workspace: &WorkspaceItem| WorkspaceItem {
+            id: store::new_id(),
+            children: Vec::new(),
+            todos: Vec::new(),
+            ..workspace.clone()
+        };
+        let root = self
+            .slot_map_store
+            .workspaces_map
+            .insert(copy(&self.slot_map_store.workspaces_map[workspace_key]));
+        let mut stack = vec![(workspace_key, root)];
+        while let Some((old_key, key)) = stack.pop() {
+            let old_workspace = self.slot_map_store.workspaces_map[old_key].clone();
+            for child_key in old_workspace.children.iter() {
+                let child = self
+                    .slot_map_store
+                    .workspaces_map
+                    .insert(copy(&self.slot_map_store.workspaces_map[*child_key]));
+                self.slot_map_store.workspaces_map[key].children.push(child);
+                stack.push((*child_key, child));
+            }
+            for todo_key in old_workspace.todos.iter() {
+                let todo = self.clone_todo(*todo_key);
+                self.slot_map_store.workspaces_map[key].todos.push(todo);
+            }
+        }
+        root
     }
 
     fn paste_todo_as_child(&mut self, key: DefaultKey, selected: DefaultKey) {
+        let depth = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .find(|t| t.key == selected)
+            .unwrap()
+            .depth;
+        if self.exceeds_max_depth(depth + 1 + self.todo_subtree_depth(key)) {
+            return;
+        }
+
         let new_todos_key = self.clone_todo(key);
         let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
         todo.children.push(new_todos_key);
+        self.slot_tree_state.invalidate();
+    }
+
+    fn record_todo(&self, action: &str, key: DefaultKey) {
+        if self.safe_mode {
+            return;
+        }
+        let todo = self.slot_map_store.todos_map.get(key).unwrap();
+        activity::record(action, &todo.id, &todo.description);
     }
 
     fn delete_todo(&mut self, selected: DefaultKey) {
+        self.record_todo("deleted", selected);
         let todo_tree_item = self
             .slot_tree_state
             .todo_tree
@@ -587,6 +1708,10 @@ impl App {
     }
 
     fn delete_workspace(&mut self, selected: DefaultKey) {
+        if !self.safe_mode {
+            let workspace = self.slot_map_store.workspaces_map.get(selected).unwrap();
+            activity::record("deleted workspace", &workspace.id, &workspace.description);
+        }
         let ws_tree_item = self
             .slot_tree_state
             .ws_tree
@@ -633,9 +1758,21 @@ impl App {
 
         // Clear multi-selection when workspace changes due to deletion
         self.clear_multi_selection_when_workspace_changes();
+        self.slot_tree_state.invalidate();
     }
 
     fn paste_workspace_as_child(&mut self, key: DefaultKey, selected: DefaultKey) {
+        let depth = self
+            .slot_tree_state
+            .ws_tree
+            .iter()
+            .find(|w| w.key == selected)
+            .unwrap()
+            .depth;
+        if self.exceeds_max_depth(depth + 1 + self.workspace_subtree_depth(key)) {
+            return;
+        }
+
         let new_workspace_key = self.clone_workspace(key);
         let workspace = self
             .slot_map_store
@@ -643,10 +1780,50 @@ impl App {
             .get_mut(selected)
             .unwrap();
         workspace.children.push(new_workspace_key);
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Number of levels in the subtree rooted at `key`, counting itself.
+    fn todo_subtree_depth(&self, key: DefaultKey) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(key, 1)];
+        while let Some((key, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            let todo = self.slot_map_store.todos_map.get(key).unwrap();
+            stack.extend(todo.children.iter().map(|k| (*k, depth + 1)));
+        }
+        deepest
+    }
+
+    /// Number of levels in the workspace subtree rooted at `key`, counting itself.
+    fn workspace_subtree_depth(&self, key: DefaultKey) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(key, 1)];
+        while let Some((key, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            let workspace = self.slot_map_store.workspaces_map.get(key).unwrap();
+            stack.extend(workspace.children.iter().map(|k| (*k, depth + 1)));
+        }
+        deepest
+    }
+
+    /// Checks `depth` against the configured `max_depth`, leaving a footer
+    /// message when it is too deep.
+    fn exceeds_max_depth(&mut self, depth: usize) -> bool {
+        match self.config.max_depth {
+            Some(max_depth) if depth > max_depth => {
+                self.status_message = Some(format!(
+                    "Refusing: nesting would exceed max depth of {}",
+                    max_depth
+                ));
+                true
+            }
+            _ => false,
+        }
     }
 
     fn handle_workspace_key_event(&mut self, key: KeyEvent) {
-        let new_editing_id = self.new_editing_id.clone();
+        let new_editing_id = self.new_editing_id;
 
         if let SortingItem::Workspace(workspace_key) = self.sorting {
             match (key.modifiers, key.code) {
@@ -680,6 +1857,7 @@ impl App {
                     }
 
                     self.sorting = SortingItem::None;
+                    self.slot_tree_state.invalidate();
                 }
                 (_, KeyCode::Char('2')) => {
                     let parent_key = self
@@ -720,6 +1898,7 @@ impl App {
                     }
 
                     self.sorting = SortingItem::None;
+                    self.slot_tree_state.invalidate();
                 }
                 _ => {}
             }
@@ -727,6 +1906,13 @@ impl App {
             return;
         }
 
+        if new_editing_id.is_none() {
+            if let Some(prefix) = self.pending_key.take() {
+                self.handle_workspace_key_sequence(prefix, key);
+                return;
+            }
+        }
+
         match new_editing_id {
             Some(id) => {
                 match (key.modifiers, key.code) {
@@ -744,11 +1930,22 @@ impl App {
                 };
             }
             None => match (key.modifiers, key.code) {
-                (_, KeyCode::Esc | KeyCode::Char('q'))
-                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                // Esc drops a multi-selection before it quits
+                (_, KeyCode::Esc) if !self.slot_tree_state.multi_selected_workspaces.is_empty() => {
+                    self.slot_tree_state.multi_selected_workspaces.clear();
+                }
+                _ if self.is_quit_key(key) => self.quit(),
 
                 (_, KeyCode::Tab) => self.active_screen = Screen::Todos,
 
+                (KeyModifiers::CONTROL, KeyCode::Char('a')) => {
+                    let keys = self.slot_tree_state.ws_tree.iter().map(|w| w.key);
+                    self.slot_tree_state.multi_selected_workspaces.extend(keys);
+                }
+
+                (_, KeyCode::Char('z')) => self.pending_key = Some('z'),
+                (_, KeyCode::Char('g')) => self.pending_key = Some('g'),
+
                 (_, KeyCode::Char('j')) => {
                     let old_workspace = self.slot_tree_state.selected_workspace;
                     if let Some(selected) = self.slot_tree_state.selected_workspace {
@@ -771,6 +1968,7 @@ impl App {
                     // Clear multi-selection when workspace changes
                     if old_workspace != self.slot_tree_state.selected_workspace {
                         self.clear_multi_selection_when_workspace_changes();
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -793,6 +1991,7 @@ impl App {
                     // Clear multi-selection when workspace changes
                     if old_workspace != self.slot_tree_state.selected_workspace {
                         self.clear_multi_selection_when_workspace_changes();
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -828,6 +2027,7 @@ impl App {
                                 self.slot_map_store.root_workspaces.swap(ind, ind - 1);
                             }
                         }
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -863,6 +2063,7 @@ impl App {
                                 self.slot_map_store.root_workspaces.swap(ind, ind + 1);
                             }
                         }
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -870,6 +2071,7 @@ impl App {
                     if let Some(selected) = self.slot_tree_state.selected_workspace {
                         self.slot_tree_state.ws_opened.insert(selected);
                         self.slot_tree_state.selected_todo = None;
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -877,6 +2079,7 @@ impl App {
                     if let Some(selected) = self.slot_tree_state.selected_workspace {
                         self.slot_tree_state.ws_opened.remove(&selected);
                         self.slot_tree_state.selected_todo = None;
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -889,62 +2092,27 @@ impl App {
                 }
                 (_, KeyCode::Char('a')) => {
                     let new_item = WorkspaceItem {
-                        id: Uuid::new_v4().to_string(),
+                        id: store::new_id(),
                         description: "".into(),
+                        color: None,
                         children: vec![],
                         todos: vec![],
                     };
                     let new_item_key = self.slot_map_store.workspaces_map.insert(new_item);
-
-                    if let Some(selected) = self.slot_tree_state.selected_workspace {
-                        // Find from rendered.
-                        let parent_key = self
-                            .slot_tree_state
-                            .ws_tree
-                            .iter()
-                            .find(|w| w.key == selected)
-                            .unwrap()
-                            .parent;
-
-                        if let Some(parent_key) = parent_key {
-                            // Nested
-                            let workspace = self
-                                .slot_map_store
-                                .workspaces_map
-                                .get_mut(parent_key)
-                                .unwrap();
-                            let ind = workspace
-                                .children
-                                .iter()
-                                .position(|k| *k == selected)
-                                .unwrap();
-                            workspace.children.insert(ind + 1, new_item_key);
-                        } else {
-                            // Top level
-                            let ind = self
-                                .slot_map_store
-                                .root_workspaces
-                                .iter()
-                                .position(|k| *k == selected)
-                                .unwrap();
-                            self.slot_map_store
-                                .root_workspaces
-                                .insert(ind + 1, new_item_key);
-                        }
-                    } else {
-                        self.slot_map_store.root_workspaces.push(new_item_key);
-                    }
+                    self.insert_workspace_after_selected(new_item_key);
                     self.input = Input::new("".into());
                     self.new_editing_id = Some(new_item_key);
                     self.slot_tree_state.selected_workspace = Some(new_item_key);
+                    self.slot_tree_state.invalidate();
                 }
                 (_, KeyCode::Char('A')) => {
                     if let Some(selected) = self.slot_tree_state.selected_workspace {
                         self.slot_tree_state.ws_opened.insert(selected);
 
                         let new_item = WorkspaceItem {
-                            id: Uuid::new_v4().to_string(),
+                            id: store::new_id(),
                             description: "".into(),
+                            color: None,
                             children: vec![],
                             todos: vec![],
                         };
@@ -959,6 +2127,7 @@ impl App {
                         self.input = Input::new("".into());
                         self.new_editing_id = Some(new_item_key);
                         self.slot_tree_state.selected_workspace = Some(new_item_key);
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -968,6 +2137,49 @@ impl App {
                     }
                 }
 
+                (_, KeyCode::Char('C')) => {
+                    // Cycle through the palette and back to no color
+                    if let Some(selected) = self.slot_tree_state.selected_workspace {
+                        let workspace = self
+                            .slot_map_store
+                            .workspaces_map
+                            .get_mut(selected)
+                            .unwrap();
+                        let next = match &workspace.color {
+                            None => Some(0),
+                            Some(color) => WORKSPACE_COLORS
+                                .iter()
+                                .position(|c| c == color)
+                                .map(|i| i + 1)
+                                .filter(|i| *i < WORKSPACE_COLORS.len()),
+                        };
+                        workspace.color = next.map(|i| WORKSPACE_COLORS[i].to_string());
+                    }
+                }
+                (_, KeyCode::Char('c')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_workspace {
+                        self.toggle_workspace_completion(selected);
+                    }
+                }
+                (_, KeyCode::Char('M')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_workspace {
+                        self.move_marked_todos(selected);
+                    }
+                }
+                (_, KeyCode::Char('I')) => {
+                    self.command_mode = true;
+                    self.command_str = "import-md ".to_string();
+                }
+                (_, KeyCode::Char('D')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_workspace {
+                        let copy = self.clone_workspace(selected);
+                        self.insert_workspace_after_selected(copy);
+                        self.slot_tree_state.selected_workspace = Some(copy);
+                        self.clear_multi_selection_when_workspace_changes();
+                        self.slot_tree_state.invalidate();
+                    }
+                }
+
                 (_, KeyCode::Char('y')) => {
                     if !self.slot_tree_state.multi_selected_workspaces.is_empty() {
                         // Copy multi-selected workspaces to clipboard
@@ -983,30 +2195,32 @@ impl App {
                     }
                 }
 
-                (_, KeyCode::Char('p')) => {
-                    if !self.clipboard_workspaces.is_empty() {
-                        self.paste_multi_selected_workspaces_at_cursor();
-                    }
+                (_, KeyCode::Char('p')) if !self.clipboard_workspaces.is_empty() => {
+                    self.paste_multi_selected_workspaces_at_cursor();
                 }
 
-                (_, KeyCode::Char('P')) => {
-                    // Note that P is only supported for single workspace paste.
-                    if !self.clipboard_workspaces.is_empty() {
-                        if let Some(selected) = self.slot_tree_state.selected_workspace {
-                            // Paste the first workspace from clipboard as child
-                            let clipboard_ws_key = self.clipboard_workspaces[0];
-                            self.paste_workspace_as_child(clipboard_ws_key, selected);
-                        }
+                // Note that P is only supported for single workspace paste.
+                (_, KeyCode::Char('P')) if !self.clipboard_workspaces.is_empty() => {
+                    if let Some(selected) = self.slot_tree_state.selected_workspace {
+                        // Paste the first workspace from clipboard as child
+                        let clipboard_ws_key = self.clipboard_workspaces[0];
+                        self.paste_workspace_as_child(clipboard_ws_key, selected);
                     }
                 }
 
                 (_, KeyCode::Char('x')) => {
                     if !self.slot_tree_state.multi_selected_workspaces.is_empty() {
-                        self.cut_multi_selected_workspaces();
+                        if self.config.confirm_multi_cut.unwrap_or(true) {
+                            self.confirmation = Some(Confirmation::CutWorkspaces);
+                        } else {
+                            self.cut_multi_selected_workspaces();
+                        }
                     } else if let Some(selected) = self.slot_tree_state.selected_workspace {
-                        self.clipboard_workspaces.clear();
-                        self.clipboard_workspaces = vec![selected];
-                        self.delete_workspace(selected);
+                        if self.config.confirm_cut.unwrap_or(true) {
+                            self.confirmation = Some(Confirmation::CutWorkspace(selected));
+                        } else {
+                            self.cut_workspace(selected);
+                        }
                     }
                 }
 
@@ -1035,6 +2249,28 @@ impl App {
     }
 
     fn handle_todos_key_event(&mut self, key: KeyEvent) {
+        if self.filter_mode {
+            match key.code {
+                KeyCode::Char(c) => {
+                    self.filter_str.push(c);
+                    self.update_filter();
+                }
+                KeyCode::Backspace => {
+                    self.filter_str.pop();
+                    self.update_filter();
+                }
+                // Keep the filter but go back to the usual keys
+                KeyCode::Enter => self.filter_mode = false,
+                KeyCode::Esc => {
+                    self.filter_mode = false;
+                    self.filter_str.clear();
+                    self.update_filter();
+                }
+                _ => {}
+            }
+            return;
+        }
+
         if self.search_mode {
             match key.code {
                 KeyCode::Char(c) => {
@@ -1045,6 +2281,10 @@ impl App {
                     self.search_str.pop();
                     self.update_search_matches();
                 }
+                KeyCode::Tab => {
+                    self.search_global = !self.search_global;
+                    self.update_search_matches();
+                }
                 KeyCode::Esc | KeyCode::Enter => {
                     self.search_mode = false;
                     self.search_str.clear();
@@ -1056,8 +2296,10 @@ impl App {
 
         if key.code == KeyCode::Char('/') {
             self.search_mode = true;
+            self.search_global = false;
             self.search_str.clear();
             self.search_matches.clear();
+            self.search_workspace_hits = None;
             self.current_match_index = 0;
             return;
         }
@@ -1102,6 +2344,7 @@ impl App {
                     }
 
                     self.sorting = SortingItem::None;
+                    self.slot_tree_state.invalidate();
                 }
                 (_, KeyCode::Char(n)) => {
                     let parent_key = self
@@ -1147,6 +2390,7 @@ impl App {
                     }
 
                     self.sorting = SortingItem::None;
+                    self.slot_tree_state.invalidate();
                 }
                 _ => {}
             }
@@ -1154,16 +2398,34 @@ impl App {
             return;
         }
 
-        let new_editing_id = self.new_editing_id.clone();
+        if self.new_editing_id.is_none() {
+            if let Some(prefix) = self.pending_key.take() {
+                self.handle_todos_key_sequence(prefix, key);
+                return;
+            }
+        }
+
+        let new_editing_id = self.new_editing_id;
         match new_editing_id {
             Some(id) => {
                 match (key.modifiers, key.code) {
                     (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
 
                     (_, KeyCode::Esc) | (_, KeyCode::Enter) => {
+                        let description = self.input.value().to_string();
                         let todo = self.slot_map_store.todos_map.get_mut(id).unwrap();
-                        todo.description = self.input.value().to_string();
+                        // New todos start out without a description
+                        let action = if todo.description.is_empty() {
+                            "created"
+                        } else {
+                            "edited"
+                        };
+                        let changed = todo.description != description;
+                        todo.description = description;
                         self.new_editing_id = None;
+                        if changed {
+                            self.record_todo(action, id);
+                        }
                     }
 
                     _ => {
@@ -1172,11 +2434,23 @@ impl App {
                 };
             }
             None => match (key.modifiers, key.code) {
-                (_, KeyCode::Esc | KeyCode::Char('q'))
-                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                // Esc drops a multi-selection before it quits
+                (_, KeyCode::Esc) if !self.slot_tree_state.multi_selected_todos.is_empty() => {
+                    self.slot_tree_state.multi_selected_todos.clear();
+                }
+                _ if self.is_quit_key(key) => self.quit(),
 
                 (_, KeyCode::Tab) => self.active_screen = Screen::Workspaces,
 
+                // Every todo listed, so collapsed children are left out
+                (KeyModifiers::CONTROL, KeyCode::Char('a')) => {
+                    let keys = self.slot_tree_state.todo_tree.iter().map(|t| t.key);
+                    self.slot_tree_state.multi_selected_todos.extend(keys);
+                }
+
+                (_, KeyCode::Char('z')) => self.pending_key = Some('z'),
+                (_, KeyCode::Char('g')) => self.pending_key = Some('g'),
+
                 (_, KeyCode::Char('j')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
                         let index = self
@@ -1210,15 +2484,20 @@ impl App {
                     }
                 }
 
+                (KeyModifiers::ALT, KeyCode::Char('l')) => self.indent_selected_todo(),
+                (KeyModifiers::ALT, KeyCode::Char('h')) => self.outdent_selected_todo(),
+
                 (_, KeyCode::Char('l')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
                         self.slot_tree_state.todo_opened.insert(selected);
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
                 (_, KeyCode::Char('h')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
                         self.slot_tree_state.todo_opened.remove(&selected);
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -1229,14 +2508,49 @@ impl App {
                         self.new_editing_id = Some(selected);
                     }
                 }
+                (_, KeyCode::Char('d')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let todo = self.slot_map_store.todos_map.get(selected).unwrap();
+                        self.input =
+                            Input::new(todo.due.map(dates::format_due).unwrap_or_default());
+                        self.date_editing = Some((selected, DateField::Due));
+                    }
+                }
+                (_, KeyCode::Char('S')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let todo = self.slot_map_store.todos_map.get(selected).unwrap();
+                        self.input = Input::new(
+                            todo.snooze_until.map(dates::format_due).unwrap_or_default(),
+                        );
+                        self.date_editing = Some((selected, DateField::Snooze));
+                    }
+                }
+                (_, KeyCode::Char('t')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let todo = self.slot_map_store.todos_map.get(selected).unwrap();
+                        self.input = Input::new(todo.tags.join(" "));
+                        self.tags_editing = Some(selected);
+                    }
+                }
+                (_, KeyCode::Char('Z')) => {
+                    self.slot_tree_state.show_snoozed = !self.slot_tree_state.show_snoozed;
+                    self.slot_tree_state.invalidate();
+                }
+                (_, KeyCode::Char('C')) => {
+                    self.slot_tree_state.hide_completed = !self.slot_tree_state.hide_completed;
+                    self.slot_tree_state.invalidate();
+                }
                 (_, KeyCode::Char('a')) => {
                     let new_item = TodoItem {
-                        id: Uuid::new_v4().to_string(),
+                        id: store::new_id(),
                         description: "".into(),
                         children: vec![],
                         due: None,
                         effort: 0,
-                        pending: true,
+                        status: Status::Todo,
+                        pinned: false,
+                        snooze_until: None,
+                        tags: Vec::new(),
                         urgency: 0,
                     };
                     let new_item_key = self.slot_map_store.todos_map.insert(new_item);
@@ -1279,18 +2593,22 @@ impl App {
                     self.input = Input::new("".into());
                     self.new_editing_id = Some(new_item_key);
                     self.slot_tree_state.selected_todo = Some(new_item_key);
+                    self.slot_tree_state.invalidate();
                 }
                 (_, KeyCode::Char('A')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
                         self.slot_tree_state.todo_opened.insert(selected);
 
                         let new_item = TodoItem {
-                            id: Uuid::new_v4().to_string(),
+                            id: store::new_id(),
                             description: "".into(),
                             children: vec![],
                             due: None,
                             effort: 0,
-                            pending: true,
+                            status: Status::Todo,
+                            pinned: false,
+                            snooze_until: None,
+                            tags: Vec::new(),
                             urgency: 0,
                         };
 
@@ -1302,27 +2620,105 @@ impl App {
                         self.input = Input::new("".into());
                         self.new_editing_id = Some(new_item_key);
                         self.slot_tree_state.selected_todo = Some(new_item_key);
+                        self.slot_tree_state.invalidate();
                     }
                 }
                 (_, KeyCode::Char('c')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
                         let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
-                        todo.pending = !todo.pending;
+                        let previous = todo.status;
+                        todo.status = todo.status.next();
+                        let done = todo.status.is_done();
+                        self.slot_tree_state.invalidate();
+                        self.last_completion = None;
+                        self.update_parent_statuses(selected);
+                        if done {
+                            self.record_todo("completed", selected);
+                            let moved = self.move_completed_todo(selected);
+                            self.last_completion = Some(Completion {
+                                key: selected,
+                                previous,
+                                at: Instant::now(),
+                                moved,
+                            });
+                        }
+                    }
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('f')) => self.filter_mode = true,
+                (_, KeyCode::Char('f')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        self.slot_tree_state.focused_todo = Some(selected);
+                        self.slot_tree_state.invalidate();
+                    }
+                }
+                (_, KeyCode::Char('F')) => {
+                    self.slot_tree_state.focused_todo = None;
+                    self.slot_tree_state.invalidate();
+                }
+                (_, KeyCode::Char('#')) => self.show_child_counts = !self.show_child_counts,
+                (_, KeyCode::Char('u')) => {
+                    if let Some(completion) = self.last_completion.take() {
+                        if completion.at.elapsed() < UNDO_COMPLETION_TIMEOUT {
+                            let todo = self
+                                .slot_map_store
+                                .todos_map
+                                .get_mut(completion.key)
+                                .unwrap();
+                            todo.status = completion.previous;
+                            if let Some(moved) = completion.moved {
+                                self.undo_completion_move(completion.key, moved);
+                            }
+                            self.update_parent_statuses(completion.key);
+                            self.slot_tree_state.invalidate();
+                        }
                     }
                 }
                 (_, KeyCode::Char('+')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let wrap = self.config.wrap_priority.unwrap_or(false);
                         let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
-                        if todo.urgency < 3 {
+                        if todo.urgency < self.theme.max_urgency() {
                             todo.urgency += 1;
+                        } else if wrap {
+                            todo.urgency = 0;
                         }
                     }
                 }
                 (_, KeyCode::Char('_')) => {
                     if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let wrap = self.config.wrap_priority.unwrap_or(false);
                         let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
                         if todo.urgency > 0 {
-                            todo.urgency -= 1;
+                            todo.urgency = todo.urgency.min(self.theme.max_urgency()) - 1;
+                        } else if wrap {
+                            todo.urgency = self.theme.max_urgency();
+                        }
+                    }
+                }
+                (_, KeyCode::Char('>')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
+                        todo.effort += 1;
+                        self.slot_tree_state.invalidate();
+                    }
+                }
+                (_, KeyCode::Char('<')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
+                        todo.effort = todo.effort.saturating_sub(1);
+                        self.slot_tree_state.invalidate();
+                    }
+                }
+                // 1 is the lowest priority
+                (_, KeyCode::Char(c @ '1'..='9')) => {
+                    let urgency = c as usize - '1' as usize;
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        if urgency <= self.theme.max_urgency() {
+                            self.slot_map_store
+                                .todos_map
+                                .get_mut(selected)
+                                .unwrap()
+                                .urgency = urgency;
                         }
                     }
                 }
@@ -1331,6 +2727,24 @@ impl App {
                         self.sorting = SortingItem::Todo(selected)
                     }
                 }
+                (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        let todo = self.slot_map_store.todos_map.get_mut(selected).unwrap();
+                        todo.pinned = !todo.pinned;
+                        self.float_pinned_siblings(selected);
+                    }
+                }
+                (_, KeyCode::Char('Y')) => self.copy_view_to_clipboard(),
+                (_, KeyCode::Char('M')) => self.mark_todos_for_move(),
+                (_, KeyCode::Char('m')) => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        self.picker = Some(Picker {
+                            moving: selected,
+                            query: String::new(),
+                            selected: 0,
+                        });
+                    }
+                }
                 (_, KeyCode::Char('y')) => {
                     if !self.slot_tree_state.multi_selected_todos.is_empty() {
                         // Copy multi-selected todos to clipboard
@@ -1346,29 +2760,32 @@ impl App {
                     }
                 }
 
-                (_, KeyCode::Char('p')) => {
-                    if !self.clipboard_todos.is_empty() {
-                        self.paste_multi_selected_todos_at_cursor();
-                    }
+                (_, KeyCode::Char('p')) if !self.clipboard_todos.is_empty() => {
+                    self.paste_multi_selected_todos_at_cursor();
                 }
 
-                (_, KeyCode::Char('P')) => {
-                    if !self.clipboard_todos.is_empty() {
-                        if let Some(selected) = self.slot_tree_state.selected_todo {
-                            // Paste the first todo from clipboard as child
-                            let clipboard_key = self.clipboard_todos[0];
-                            self.paste_todo_as_child(clipboard_key, selected);
-                        }
+                (_, KeyCode::Char('P')) if !self.clipboard_todos.is_empty() => {
+                    if let Some(selected) = self.slot_tree_state.selected_todo {
+                        // Paste the first todo from clipboard as child
+                        let clipboard_key = self.clipboard_todos[0];
+                        self.paste_todo_as_child(clipboard_key, selected);
                     }
                 }
 
                 (_, KeyCode::Char('x')) => {
                     if !self.slot_tree_state.multi_selected_todos.is_empty() {
-                        self.cut_multi_selected_todos();
+                        if self.config.confirm_multi_cut.unwrap_or(true) {
+                            self.confirmation = Some(Confirmation::CutTodos);
+                        } else {
+                            self.cut_multi_selected_todos();
+                        }
                     } else if let Some(selected) = self.slot_tree_state.selected_todo {
-                        self.clipboard_todos.clear();
-                        self.clipboard_todos.push(selected);
-                        self.delete_todo(selected);
+                        let children = &self.slot_map_store.todos_map[selected].children;
+                        if !children.is_empty() && self.config.confirm_cut.unwrap_or(true) {
+                            self.confirmation = Some(Confirmation::CutTodo(selected));
+                        } else {
+                            self.cut_todo(selected);
+                        }
                     }
                 }
 
@@ -1402,6 +2819,7 @@ impl App {
                                 workspace.todos.swap(ind, ind - 1);
                             }
                         }
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -1435,6 +2853,7 @@ impl App {
                                 workspace.todos.swap(ind, ind + 1);
                             }
                         }
+                        self.slot_tree_state.invalidate();
                     }
                 }
 
@@ -1453,53 +2872,1887 @@ impl App {
                     }
                 }
 
-                (_, KeyCode::Char('n')) => {
-                    if !self.search_matches.is_empty() {
-                        self.current_match_index =
-                            (self.current_match_index + 1) % self.search_matches.len();
+                (_, KeyCode::Char('n')) if !self.search_matches.is_empty() => {
+                    self.current_match_index =
+                        (self.current_match_index + 1) % self.search_matches.len();
+                    self.select_current_match();
+                }
+                (_, KeyCode::Char('N')) if !self.search_matches.is_empty() => {
+                    let len = self.search_matches.len();
+                    self.current_match_index = (self.current_match_index + len - 1) % len;
+                    self.select_current_match();
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// Selects the search match at `current_match_index`, switching to its
+    /// workspace first.
+    fn select_current_match(&mut self) {
+        // Global matches may live in another workspace
+        let (workspace_key, todo_key) = match self.search_matches[self.current_match_index] {
+            SearchMatch::Workspace(workspace) => (workspace, None),
+            SearchMatch::Todo { workspace, todo } => (workspace, Some(todo)),
+        };
+        if self.slot_tree_state.selected_workspace != Some(workspace_key) {
+            self.slot_tree_state.selected_workspace = Some(workspace_key);
+            self.clear_multi_selection_when_workspace_changes();
+            self.slot_tree_state
+                .update_workspace_tree_state(&self.slot_map_store);
+        }
+
+        // Select the todo if it's in the tree
+        self.slot_tree_state.selected_todo = match todo_key {
+            Some(todo_key)
+                if self
+                    .slot_tree_state
+                    .todo_tree
+                    .iter()
+                    .any(|t| t.key == todo_key) =>
+            {
+                Some(todo_key)
+            }
+            Some(_) => self.slot_tree_state.selected_todo,
+            None => None,
+        };
+    }
+
+    /// Copies the todo pane as it's currently shown, collapsed todos left
+    /// out, to the system clipboard as indented plain text.
+    fn copy_view_to_clipboard(&mut self) {
+        let mut text = String::new();
+        for t in &self.slot_tree_state.todo_tree {
+            let todo = self.slot_map_store.todos_map.get(t.key).unwrap();
+            let depth = t.last_child.len() - 1;
+            text.push_str(&store::outline_line(depth, todo.status, &todo.description));
+        }
+
+        if text.is_empty() {
+            self.status_message = Some("Nothing to copy".to_string());
+            return;
+        }
+
+        self.status_message = Some(match self.copy_to_system_clipboard(text) {
+            Ok(()) => "Copied the todo list to the clipboard".to_string(),
+            Err(message) => message,
+        });
+    }
+
+    fn copy_to_system_clipboard(&mut self, text: String) -> Result<(), String> {
+        self.system_clipboard()?
+            .set_text(text)
+            .map_err(|err| format!("Could not copy to the clipboard: {}", err))
+    }
+
+    /// The system clipboard, opened on first use.
+    fn system_clipboard(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.system_clipboard.is_none() {
+            let clipboard = arboard::Clipboard::new()
+                .map_err(|err| format!("Clipboard unavailable: {}", err))?;
+            self.system_clipboard = Some(clipboard);
+        }
+        Ok(self.system_clipboard.as_mut().unwrap())
+    }
+
+    /// Copies the selected todo and everything under it to the system
+    /// clipboard as JSON, in the same form as the data file.
+    fn copy_todo_as_json(&mut self) {
+        let Some(selected) = self.slot_tree_state.selected_todo else {
+            return;
+        };
+        let todo = self.slot_map_store.create_todo(selected);
+        let json = serde_json::to_string_pretty(&todo).unwrap();
+        self.status_message = Some(match self.copy_to_system_clipboard(json) {
+            Ok(()) => format!("Copied '{}' as JSON", todo.description),
+            Err(message) => message,
+        });
+    }
+
+    /// Copies the selected todo and everything under it to the system
+    /// clipboard as a Markdown checklist, to paste as text elsewhere.
+    fn copy_todo_as_markdown(&mut self) {
+        let Some(selected) = self.slot_tree_state.selected_todo else {
+            return;
+        };
+        let todo = self.slot_map_store.create_todo(selected);
+        self.status_message = Some(match self.copy_to_system_clipboard(todo.to_markdown()) {
+            Ok(()) => format!("Copied '{}' as Markdown", todo.description),
+            Err(message) => message,
+        });
+    }
+
+    /// Adds the todo JSON on the system clipboard, as copied by `gy`, under the
+    /// selected todo, or at the end of the workspace's todos when none is
+    /// selected. It gets new ids so pasting twice doesn't create duplicates.
+    fn paste_todo_from_json(&mut self) {
+        if self.slot_tree_state.selected_workspace.is_none() {
+            return;
+        }
+        let Some(text) = self.read_system_clipboard() else {
+            return;
+        };
+        let mut todo: store::Todo = match serde_json::from_str(&text) {
+            Ok(todo) => todo,
+            Err(err) => {
+                self.status_message = Some(format!("The clipboard doesn't hold a todo: {}", err));
+                return;
+            }
+        };
+
+        let mut stack = vec![&mut todo];
+        while let Some(todo) = stack.pop() {
+            todo.id = store::new_id();
+            stack.extend(todo.children.iter_mut());
+        }
+
+        self.paste_todos(std::slice::from_ref(&todo));
+    }
+
+    /// Adds a todo per line of the text on the system clipboard, nested by
+    /// indentation as with `doneit add`, under the selected todo or at the
+    /// end of the workspace's todos when none is selected. Top level lines
+    /// that would nest deeper than `max_depth`, with their children, are
+    /// skipped.
+    fn paste_text_from_clipboard(&mut self) {
+        if self.slot_tree_state.selected_workspace.is_none() {
+            return;
+        }
+        let Some(text) = self.read_system_clipboard() else {
+            return;
+        };
+        let todos = store::parse_outline(&text);
+        if todos.is_empty() {
+            self.status_message = Some("The clipboard holds no text to paste".to_string());
+            return;
+        }
+        self.paste_todos(&todos);
+    }
+
+    /// Adds `todos` as [`App::attach_pasted_todo`] does, selecting the first
+    /// one and reporting the ones left out.
+    fn paste_todos(&mut self, todos: &[store::Todo]) {
+        let mut pasted = Vec::new();
+        for todo in todos {
+            if let Some(key) = self.attach_pasted_todo(todo) {
+                self.record_todo("pasted", key);
+                pasted.push(key);
+            }
+        }
+        // Nothing pasted leaves the refusal from `exceeds_max_depth`
+        let Some(first) = pasted.first() else {
+            return;
+        };
+        self.slot_tree_state.selected_todo = Some(*first);
+        self.slot_tree_state.invalidate();
+        let mut message = format!(
+            "Pasted {} todo{}",
+            pasted.len(),
+            if pasted.len() == 1 { "" } else { "s" }
+        );
+        let skipped = todos.len() - pasted.len();
+        if skipped > 0 {
+            message.push_str(&format!(
+                ", skipped {} that would nest deeper than max_depth",
+                skipped
+            ));
+        }
+        self.status_message = Some(message);
+    }
+
+    /// The text on the system clipboard, or `None` with the reason in the
+    /// footer.
+    fn read_system_clipboard(&mut self) -> Option<String> {
+        match self.system_clipboard().and_then(|clipboard| {
+            clipboard
+                .get_text()
+                .map_err(|err| format!("Could not read the clipboard: {}", err))
+        }) {
+            Ok(text) => Some(text),
+            Err(message) => {
+                self.status_message = Some(message);
+                None
+            }
+        }
+    }
+
+    /// Inserts `todo` under the selected todo, or at the end of the selected
+    /// workspace's todos. Returns `None`, inserting nothing, when that would
+    /// nest deeper than `max_depth`.
+    fn attach_pasted_todo(&mut self, todo: &store::Todo) -> Option<DefaultKey> {
+        // Levels it would take up once attached, counting its new parents
+        let depth = match self.slot_tree_state.selected_todo {
+            Some(selected) => {
+                self.slot_tree_state
+                    .todo_tree
+                    .iter()
+                    .find(|t| t.key == selected)
+                    .unwrap()
+                    .depth
+                    + 1
+            }
+            None => 0,
+        };
+        if self.exceeds_max_depth(depth + todo.depth()) {
+            return None;
+        }
+        let key = self.slot_map_store.insert_todo(todo);
+        match self.slot_tree_state.selected_todo {
+            Some(selected) => {
+                let parent = self.slot_map_store.todos_map.get_mut(selected).unwrap();
+                parent.children.push(key);
+                self.slot_tree_state.todo_opened.insert(selected);
+            }
+            None => {
+                let workspace = self.slot_tree_state.selected_workspace.unwrap();
+                self.slot_map_store
+                    .workspaces_map
+                    .get_mut(workspace)
+                    .unwrap()
+                    .todos
+                    .push(key)
+            }
+        }
+        Some(key)
+    }
+
+    /// With `auto_complete_parents`, marks the ancestors of `key` done once all
+    /// of their children are, and reopens done ones that no longer are.
+    fn update_parent_statuses(&mut self, key: DefaultKey) {
+        if !self.config.auto_complete_parents.unwrap_or(false) {
+            return;
+        }
+        let Some(workspace) = self.slot_tree_state.selected_workspace else {
+            return;
+        };
+        let roots = &self.slot_map_store.workspaces_map[workspace].todos;
+        let Some(ancestors) = find_todo_path(&self.slot_map_store.todos_map, roots, key) else {
+            return;
+        };
+        // Nearest first, stopping at the first one that stays as it is
+        for parent in ancestors.into_iter().rev() {
+            let todos_map = &self.slot_map_store.todos_map;
+            let all_done = todos_map[parent]
+                .children
+                .iter()
+                .all(|child| todos_map[*child].status.is_done());
+            let todo = self.slot_map_store.todos_map.get_mut(parent).unwrap();
+            if all_done == todo.status.is_done() {
+                break;
+            }
+            if all_done {
+                todo.status = Status::Done;
+                self.record_todo("completed", parent);
+            } else {
+                todo.status = Status::Todo;
+            }
+        }
+    }
+
+    /// Marks the todos of a workspace done, or reopens them all when most of
+    /// them are done already.
+    fn toggle_workspace_completion(&mut self, workspace_key: DefaultKey) {
+        let recursive = self.config.complete_workspace_recursively.unwrap_or(true);
+
+        let mut todos = Vec::new();
+        let mut workspaces = vec![workspace_key];
+        while let Some(key) = workspaces.pop() {
+            let workspace = self.slot_map_store.workspaces_map.get(key).unwrap();
+            let mut stack = workspace.todos.clone();
+            while let Some(todo_key) = stack.pop() {
+                todos.push(todo_key);
+                if recursive {
+                    let todo = self.slot_map_store.todos_map.get(todo_key).unwrap();
+                    stack.extend(&todo.children);
+                }
+            }
+            if recursive {
+                workspaces.extend(&workspace.children);
+            }
+        }
+
+        if todos.is_empty() {
+            return;
+        }
+
+        let done = todos
+            .iter()
+            .filter(|key| {
+                self.slot_map_store
+                    .todos_map
+                    .get(**key)
+                    .unwrap()
+                    .status
+                    .is_done()
+            })
+            .count();
+        let status = if done * 2 > todos.len() {
+            Status::Todo
+        } else {
+            Status::Done
+        };
+        for key in &todos {
+            self.slot_map_store.todos_map.get_mut(*key).unwrap().status = status;
+            self.record_todo(
+                if status.is_done() {
+                    "completed"
+                } else {
+                    "reopened"
+                },
+                *key,
+            );
+        }
+        self.slot_tree_state.invalidate();
+
+        self.status_message = Some(format!(
+            "{} {} todo{}",
+            if status.is_done() {
+                "Completed"
+            } else {
+                "Reopened"
+            },
+            todos.len(),
+            if todos.len() == 1 { "" } else { "s" }
+        ));
+    }
+
+    /// Todos of the selected workspace matching the picker's query, with their
+    /// depth. The todo being moved and its descendants are left out, moving a
+    /// todo under itself would create a cycle.
+    fn picker_candidates(&self, picker: &Picker) -> Vec<(DefaultKey, usize)> {
+        let Some(workspace_key) = self.slot_tree_state.selected_workspace else {
+            return Vec::new();
+        };
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(workspace_key)
+            .unwrap();
+        let query = picker.query.to_lowercase();
+
+        let mut candidates = Vec::new();
+        let mut stack: Vec<(DefaultKey, usize)> =
+            workspace.todos.iter().rev().map(|k| (*k, 0)).collect();
+        while let Some((key, depth)) = stack.pop() {
+            if key == picker.moving {
+                continue;
+            }
+            let todo = self.slot_map_store.todos_map.get(key).unwrap();
+            if todo.description.to_lowercase().contains(&query) {
+                candidates.push((key, depth));
+            }
+            stack.extend(todo.children.iter().rev().map(|k| (*k, depth + 1)));
+        }
+        candidates
+    }
+
+    fn handle_picker_key_event(&mut self, key: KeyEvent) {
+        let Some(mut picker) = self.picker.take() else {
+            return;
+        };
+        let candidates = self.picker_candidates(&picker);
+
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) => return,
+            (_, KeyCode::Enter) => {
+                if let Some(&(parent, depth)) = candidates.get(picker.selected) {
+                    self.move_todo_under(picker.moving, parent, depth);
+                }
+                return;
+            }
+            (_, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+                picker.selected = (picker.selected + 1).min(candidates.len().saturating_sub(1));
+            }
+            (_, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+                picker.selected = picker.selected.saturating_sub(1);
+            }
+            (_, KeyCode::Backspace) => {
+                picker.query.pop();
+                picker.selected = 0;
+            }
+            (_, KeyCode::Char(c)) => {
+                picker.query.push(c);
+                picker.selected = 0;
+            }
+            _ => {}
+        }
+
+        self.picker = Some(picker);
+    }
+
+    /// Splices `key` and its subtree out of its current place and appends it
+    /// to the children of `parent`, which sits at `parent_depth`.
+    fn move_todo_under(&mut self, key: DefaultKey, parent: DefaultKey, parent_depth: usize) {
+        if self.slot_map_store.is_todo_descendant(key, parent) {
+            self.status_message =
+                Some("Can't move a todo under itself or its children".to_string());
+            return;
+        }
+        if self.exceeds_max_depth(parent_depth + 1 + self.todo_subtree_depth(key)) {
+            return;
+        }
+
+        let workspace_key = self.slot_tree_state.selected_workspace.unwrap();
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(workspace_key)
+            .unwrap();
+        let path = find_todo_path(&self.slot_map_store.todos_map, &workspace.todos, key).unwrap();
+
+        let siblings = match path.last() {
+            Some(old_parent) => {
+                &mut self
+                    .slot_map_store
+                    .todos_map
+                    .get_mut(*old_parent)
+                    .unwrap()
+                    .children
+            }
+            None => {
+                &mut self
+                    .slot_map_store
+                    .workspaces_map
+                    .get_mut(workspace_key)
+                    .unwrap()
+                    .todos
+            }
+        };
+        siblings.retain(|k| *k != key);
+
+        let parent_todo = self.slot_map_store.todos_map.get_mut(parent).unwrap();
+        parent_todo.children.push(key);
+
+        // Keep the moved todo in view
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(workspace_key)
+            .unwrap();
+        if let Some(path) = find_todo_path(&self.slot_map_store.todos_map, &workspace.todos, key) {
+            self.slot_tree_state.todo_opened.extend(path);
+        }
+        self.slot_tree_state.selected_todo = Some(key);
+        self.slot_tree_state.invalidate();
+        self.record_todo("moved", key);
+    }
+
+    /// Moves pinned todos to the front of `key`'s sibling list, keeping the
+    /// stored order in line with the order they're drawn in.
+    fn float_pinned_siblings(&mut self, key: DefaultKey) {
+        let parent = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .find(|t| t.key == key)
+            .and_then(|t| t.parent);
+
+        let mut siblings = match parent {
+            Some(parent) => self
+                .slot_map_store
+                .todos_map
+                .get(parent)
+                .unwrap()
+                .children
+                .clone(),
+            None => match self.slot_tree_state.selected_workspace {
+                Some(workspace) => self
+                    .slot_map_store
+                    .workspaces_map
+                    .get(workspace)
+                    .unwrap()
+                    .todos
+                    .clone(),
+                None => return,
+            },
+        };
+
+        siblings = pinned_first(&self.slot_map_store.todos_map, &siblings);
+
+        match parent {
+            Some(parent) => {
+                self.slot_map_store
+                    .todos_map
+                    .get_mut(parent)
+                    .unwrap()
+                    .children = siblings
+            }
+            None => {
+                let workspace = self.slot_tree_state.selected_workspace.unwrap();
+                self.slot_map_store
+                    .workspaces_map
+                    .get_mut(workspace)
+                    .unwrap()
+                    .todos = siblings;
+            }
+        }
+
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Handles the second key of a two key sequence such as `zz`.
+    fn handle_workspace_key_sequence(&mut self, prefix: char, key: KeyEvent) {
+        match (prefix, key.code) {
+            ('z', KeyCode::Char('z')) => self.reveal_selected_workspace(),
+            ('z', KeyCode::Char('M')) => self.set_all_workspaces_opened(false),
+            ('z', KeyCode::Char('R')) => self.set_all_workspaces_opened(true),
+            ('g', KeyCode::Char('K')) => self.move_selected_workspace_to_end(true),
+            ('g', KeyCode::Char('J')) => self.move_selected_workspace_to_end(false),
+            _ => {}
+        }
+    }
+
+    /// Moves the selected workspace to the first or last place among its
+    /// siblings.
+    fn move_selected_workspace_to_end(&mut self, first: bool) {
+        let Some(selected) = self.slot_tree_state.selected_workspace else {
+            return;
+        };
+        let parent = self
+            .slot_tree_state
+            .ws_tree
+            .iter()
+            .find(|w| w.key == selected)
+            .unwrap()
+            .parent;
+        let siblings = match parent {
+            Some(parent) => {
+                &mut self
+                    .slot_map_store
+                    .workspaces_map
+                    .get_mut(parent)
+                    .unwrap()
+                    .children
+            }
+            None => &mut self.slot_map_store.root_workspaces,
+        };
+        move_to_end(siblings, selected, first);
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Expands every workspace with children, or collapses them all. A
+    /// selection hidden by collapsing moves up to its root workspace.
+    fn set_all_workspaces_opened(&mut self, opened: bool) {
+        self.slot_tree_state.ws_opened = if opened {
+            self.slot_map_store
+                .workspaces_map
+                .iter()
+                .filter(|(_, workspace)| !workspace.children.is_empty())
+                .map(|(key, _)| key)
+                .collect()
+        } else {
+            let ancestors = self.selected_workspace_ancestors();
+            let root = self
+                .slot_map_store
+                .root_workspaces
+                .iter()
+                .find(|key| ancestors.contains(key));
+            if let Some(root) = root {
+                self.slot_tree_state.selected_workspace = Some(*root);
+                self.slot_tree_state.selected_todo = None;
+                self.clear_multi_selection_when_workspace_changes();
+            }
+            HashSet::new()
+        };
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Collapses every workspace except the ancestors of the selected one.
+    fn reveal_selected_workspace(&mut self) {
+        self.slot_tree_state.ws_opened = self.selected_workspace_ancestors();
+        self.slot_tree_state.invalidate();
+    }
+
+    fn selected_workspace_ancestors(&self) -> HashSet<DefaultKey> {
+        let mut ancestors = HashSet::new();
+        let mut current = self.slot_tree_state.selected_workspace;
+        while let Some(key) = current {
+            current = self
+                .slot_tree_state
+                .ws_tree
+                .iter()
+                .find(|w| w.key == key)
+                .and_then(|w| w.parent);
+            if let Some(parent) = current {
+                ancestors.insert(parent);
+            }
+        }
+        ancestors
+    }
+
+    /// Handles the second key of a two key sequence such as `gx`.
+    fn handle_todos_key_sequence(&mut self, prefix: char, key: KeyEvent) {
+        match (prefix, key.code) {
+            ('z', KeyCode::Char('M')) => self.set_all_todos_opened(false),
+            ('z', KeyCode::Char('R')) => self.set_all_todos_opened(true),
+            ('g', KeyCode::Char('x')) => self.open_link(),
+            ('g', KeyCode::Char('y')) => self.copy_todo_as_json(),
+            ('g', KeyCode::Char('Y')) => self.copy_todo_as_markdown(),
+            ('g', KeyCode::Char('p')) => self.paste_todo_from_json(),
+            ('g', KeyCode::Char('P')) => self.paste_text_from_clipboard(),
+            ('g', KeyCode::Char('K')) => self.move_selected_todo_to_end(true),
+            ('g', KeyCode::Char('J')) => self.move_selected_todo_to_end(false),
+            _ => {}
+        }
+    }
+
+    /// Expands or collapses every todo of the selected workspace that has
+    /// children.
+    fn set_all_todos_opened(&mut self, opened: bool) {
+        let Some(workspace) = self.slot_tree_state.selected_workspace else {
+            return;
+        };
+        let mut pending = self.slot_map_store.workspaces_map[workspace].todos.clone();
+        while let Some(key) = pending.pop() {
+            let children = &self.slot_map_store.todos_map[key].children;
+            if children.is_empty() {
+                continue;
+            }
+            pending.extend(children);
+            if opened {
+                self.slot_tree_state.todo_opened.insert(key);
+            } else {
+                self.slot_tree_state.todo_opened.remove(&key);
+            }
+        }
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Moves the selected todo to the first or last place among its siblings.
+    fn move_selected_todo_to_end(&mut self, first: bool) {
+        let Some(selected) = self.slot_tree_state.selected_todo else {
+            return;
+        };
+        let parent = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .find(|t| t.key == selected)
+            .unwrap()
+            .parent;
+        let workspace = self.slot_tree_state.selected_workspace.unwrap();
+        move_to_end(self.siblings_mut(workspace, parent), selected, first);
+        self.slot_tree_state.invalidate();
+    }
+
+    /// The list holding the todos under `parent`, or the top level todos of
+    /// `workspace` without one.
+    fn siblings_mut(
+        &mut self,
+        workspace: DefaultKey,
+        parent: Option<DefaultKey>,
+    ) -> &mut Vec<DefaultKey> {
+        match parent {
+            Some(parent) => {
+                &mut self
+                    .slot_map_store
+                    .todos_map
+                    .get_mut(parent)
+                    .unwrap()
+                    .children
+            }
+            None => {
+                &mut self
+                    .slot_map_store
+                    .workspaces_map
+                    .get_mut(workspace)
+                    .unwrap()
+                    .todos
+            }
+        }
+    }
+
+    /// Makes the selected todo the last child of the todo above it among its
+    /// siblings.
+    fn indent_selected_todo(&mut self) {
+        let (Some(selected), Some(workspace)) = (
+            self.slot_tree_state.selected_todo,
+            self.slot_tree_state.selected_workspace,
+        ) else {
+            return;
+        };
+        // Its siblings are outside of the focused subtree
+        if self.slot_tree_state.focused_todo == Some(selected) {
+            return;
+        }
+        // The sibling shown above it, which with sorting, filters and hidden
+        // todos isn't necessarily the one stored before it
+        let tree = &self.slot_tree_state.todo_tree;
+        let index = tree.iter().position(|t| t.key == selected).unwrap();
+        let (parent, depth) = (tree[index].parent, tree[index].depth);
+        let Some(new_parent) = tree[..index]
+            .iter()
+            .rev()
+            .take_while(|t| Some(t.key) != parent)
+            .find(|t| t.parent == parent)
+            .map(|t| t.key)
+        else {
+            self.status_message = Some("Nothing above to indent under".to_string());
+            return;
+        };
+        if self.exceeds_max_depth(depth + 1 + self.todo_subtree_depth(selected)) {
+            return;
+        }
+        self.siblings_mut(workspace, parent)
+            .retain(|k| *k != selected);
+        self.slot_map_store
+            .todos_map
+            .get_mut(new_parent)
+            .unwrap()
+            .children
+            .push(selected);
+        self.slot_tree_state.todo_opened.insert(new_parent);
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Moves the selected todo out of its parent, right below it.
+    fn outdent_selected_todo(&mut self) {
+        let (Some(selected), Some(workspace)) = (
+            self.slot_tree_state.selected_todo,
+            self.slot_tree_state.selected_workspace,
+        ) else {
+            return;
+        };
+        let tree = &self.slot_tree_state.todo_tree;
+        let Some(parent) = tree.iter().find(|t| t.key == selected).unwrap().parent else {
+            return;
+        };
+        // The focused todo's parent is outside of the view
+        if self.slot_tree_state.focused_todo == Some(selected) {
+            return;
+        }
+        let grandparent = tree.iter().find(|t| t.key == parent).unwrap().parent;
+
+        self.slot_map_store
+            .todos_map
+            .get_mut(parent)
+            .unwrap()
+            .children
+            .retain(|k| *k != selected);
+        let siblings = self.siblings_mut(workspace, grandparent);
+        let index = siblings.iter().position(|k| *k == parent).unwrap();
+        siblings.insert(index + 1, selected);
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Drops what still refers to the todo `key`, before it is removed from
+    /// the store for good.
+    fn forget_todo(&mut self, key: DefaultKey) {
+        self.clipboard_todos.retain(|k| *k != key);
+        self.moving_todos.retain(|moving| moving.key != key);
+        let match_count = self.search_matches.len();
+        self.search_matches
+            .retain(|m| !matches!(m, SearchMatch::Todo { todo, .. } if *todo == key));
+        if self.search_matches.len() != match_count {
+            self.current_match_index = 0;
+        }
+        if self.last_completion.as_ref().is_some_and(|c| c.key == key) {
+            self.last_completion = None;
+        }
+        if self.last_opened_link.is_some_and(|(k, _)| k == key) {
+            self.last_opened_link = None;
+        }
+        if matches!(self.confirmation, Some(Confirmation::CutTodo(k)) if k == key) {
+            self.confirmation = None;
+        }
+        self.slot_tree_state.todo_opened.remove(&key);
+    }
+
+    /// Turns the selected todo into a workspace under the selected one, with
+    /// the todo's children as its todos, and selects it.
+    fn convert_todo_to_workspace(&mut self) {
+        let (Some(selected), Some(workspace)) = (
+            self.slot_tree_state.selected_todo,
+            self.slot_tree_state.selected_workspace,
+        ) else {
+            self.status_message = Some("No todo to convert".to_string());
+            return;
+        };
+        if !self.slot_tree_state.multi_selected_todos.is_empty() {
+            self.status_message = Some("Clear the multi-selection to convert a todo".to_string());
+            return;
+        }
+        if self.slot_tree_state.focused_todo == Some(selected) {
+            self.status_message = Some("Zoom out with F to convert the focused todo".to_string());
+            return;
+        }
+        let Some(parent) = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .find(|t| t.key == selected)
+            .map(|t| t.parent)
+        else {
+            return;
+        };
+
+        self.record_todo("converted", selected);
+        self.siblings_mut(workspace, parent)
+            .retain(|k| *k != selected);
+        self.forget_todo(selected);
+        let todo = self.slot_map_store.todos_map.remove(selected).unwrap();
+
+        let new_item = WorkspaceItem {
+            id: store::new_id(),
+            description: todo.description,
+            color: None,
+            children: vec![],
+            todos: todo.children,
+        };
+        let key = self.slot_map_store.workspaces_map.insert(new_item);
+        self.slot_map_store
+            .workspaces_map
+            .get_mut(workspace)
+            .unwrap()
+            .children
+            .push(key);
+        self.slot_tree_state.ws_opened.insert(workspace);
+        self.slot_tree_state.selected_workspace = Some(key);
+        self.slot_tree_state.selected_todo = None;
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Marks the multi-selected todos, or the selected one, to be moved to
+    /// another workspace with `M` in the workspaces pane. Todos inside
+    /// another marked one just come along with it. Marking again cancels.
+    fn mark_todos_for_move(&mut self) {
+        if !self.moving_todos.is_empty() {
+            self.moving_todos.clear();
+            return;
+        }
+        let Some(workspace) = self.slot_tree_state.selected_workspace else {
+            return;
+        };
+        let marked: HashSet<DefaultKey> = if self.slot_tree_state.multi_selected_todos.is_empty() {
+            self.slot_tree_state.selected_todo.into_iter().collect()
+        } else {
+            std::mem::take(&mut self.slot_tree_state.multi_selected_todos)
+                .into_iter()
+                .collect()
+        };
+
+        let tree = &self.slot_tree_state.todo_tree;
+        let parent_of = |key: DefaultKey| tree.iter().find(|t| t.key == key).and_then(|t| t.parent);
+        for item in tree.iter().filter(|t| marked.contains(&t.key)) {
+            let mut ancestor = item.parent;
+            while let Some(key) = ancestor {
+                if marked.contains(&key) {
+                    break;
+                }
+                ancestor = parent_of(key);
+            }
+            if ancestor.is_none() {
+                self.moving_todos.push(MovingTodo {
+                    key: item.key,
+                    workspace,
+                    parent: item.parent,
+                });
+            }
+        }
+    }
+
+    /// Moves the todos marked by `mark_todos_for_move`, with their children,
+    /// to the end of `target`'s todos. Todos that were moved or removed in
+    /// the meantime are left alone.
+    fn move_marked_todos(&mut self, target: DefaultKey) {
+        if self.moving_todos.is_empty() {
+            return;
+        }
+        let mut moved = 0;
+        for moving in std::mem::take(&mut self.moving_todos) {
+            let parent_exists = match moving.parent {
+                Some(parent) => self.slot_map_store.todos_map.contains_key(parent),
+                None => self
+                    .slot_map_store
+                    .workspaces_map
+                    .contains_key(moving.workspace),
+            };
+            if !parent_exists {
+                continue;
+            }
+            let siblings = self.siblings_mut(moving.workspace, moving.parent);
+            if !siblings.contains(&moving.key) {
+                continue;
+            }
+            siblings.retain(|k| *k != moving.key);
+            self.slot_map_store
+                .workspaces_map
+                .get_mut(target)
+                .unwrap()
+                .todos
+                .push(moving.key);
+            if self.slot_tree_state.focused_todo == Some(moving.key) {
+                self.slot_tree_state.focused_todo = None;
+            }
+            self.record_todo("moved", moving.key);
+            moved += 1;
+        }
+        self.slot_tree_state.invalidate();
+
+        let workspace = self.slot_map_store.workspaces_map.get(target).unwrap();
+        self.status_message = Some(format!(
+            "Moved {} todo{} to {}",
+            moved,
+            if moved == 1 { "" } else { "s" },
+            workspace.description
+        ));
+    }
+
+    /// Applies `complete_action` to a todo that was just completed, moving it
+    /// to the bottom of its siblings or to the root `Archive` workspace. The
+    /// focused todo and todos already in the archive stay where they are.
+    fn move_completed_todo(&mut self, key: DefaultKey) -> Option<CompletionMove> {
+        let action = self.config.complete_action.unwrap_or_default();
+        let workspace = self.slot_tree_state.selected_workspace?;
+        if action == CompleteAction::Toggle || self.slot_tree_state.focused_todo == Some(key) {
+            return None;
+        }
+        let row = self
+            .slot_tree_state
+            .todo_tree
+            .iter()
+            .position(|t| t.key == key)?;
+        let parent = self.slot_tree_state.todo_tree[row].parent;
+        let index = self
+            .siblings_mut(workspace, parent)
+            .iter()
+            .position(|k| *k == key)?;
+
+        let archive = match action {
+            CompleteAction::Toggle => return None,
+            CompleteAction::MoveToBottom => {
+                move_to_end(self.siblings_mut(workspace, parent), key, false);
+                None
+            }
+            CompleteAction::Archive => {
+                let archive = self.archive_workspace();
+                if archive == workspace {
+                    return None;
+                }
+                self.siblings_mut(workspace, parent).remove(index);
+                self.slot_map_store
+                    .workspaces_map
+                    .get_mut(archive)
+                    .unwrap()
+                    .todos
+                    .push(key);
+                self.slot_tree_state
+                    .update_workspace_tree_state(&self.slot_map_store);
+                let todo_tree = &self.slot_tree_state.todo_tree;
+                self.slot_tree_state.selected_todo = todo_tree
+                    .get(row.min(todo_tree.len().saturating_sub(1)))
+                    .map(|t| t.key);
+                Some(archive)
+            }
+        };
+        self.slot_tree_state.invalidate();
+        Some(CompletionMove {
+            workspace,
+            parent,
+            index,
+            archive,
+        })
+    }
+
+    /// The root workspace named `Archive`, added at the end if there is none.
+    fn archive_workspace(&mut self) -> DefaultKey {
+        let existing =
+            self.slot_map_store.root_workspaces.iter().find(|key| {
+                self.slot_map_store.workspaces_map[**key].description == ARCHIVE_WORKSPACE
+            });
+        if let Some(key) = existing {
+            return *key;
+        }
+        let new_item = WorkspaceItem {
+            id: store::new_id(),
+            description: ARCHIVE_WORKSPACE.into(),
+            color: None,
+            children: vec![],
+            todos: vec![],
+        };
+        let key = self.slot_map_store.workspaces_map.insert(new_item);
+        self.slot_map_store.root_workspaces.push(key);
+        key
+    }
+
+    /// Puts a todo moved by `move_completed_todo` back where it was, as long
+    /// as its old parent and the archive are still around.
+    fn undo_completion_move(&mut self, key: DefaultKey, moved: CompletionMove) {
+        let parent_exists = match moved.parent {
+            Some(parent) => self.slot_map_store.todos_map.contains_key(parent),
+            None => self
+                .slot_map_store
+                .workspaces_map
+                .contains_key(moved.workspace),
+        };
+        if !parent_exists {
+            return;
+        }
+        match moved.archive {
+            Some(archive) => match self.slot_map_store.workspaces_map.get_mut(archive) {
+                Some(archive) if archive.todos.contains(&key) => {
+                    archive.todos.retain(|k| *k != key)
+                }
+                _ => return,
+            },
+            None => self
+                .siblings_mut(moved.workspace, moved.parent)
+                .retain(|k| *k != key),
+        }
+        let siblings = self.siblings_mut(moved.workspace, moved.parent);
+        siblings.insert(moved.index.min(siblings.len()), key);
+        if self.slot_tree_state.selected_workspace == Some(moved.workspace) {
+            self.slot_tree_state.selected_todo = Some(key);
+        }
+    }
+
+    /// Opens the first URL in the selected todo's description in the default
+    /// browser. Repeating it on the same todo cycles through the other URLs.
+    fn open_link(&mut self) {
+        if let Some(selected) = self.slot_tree_state.selected_todo {
+            let todo = self.slot_map_store.todos_map.get(selected).unwrap();
+            let urls = find_urls(&todo.description);
+            if urls.is_empty() {
+                return;
+            }
+
+            let index = match self.last_opened_link {
+                Some((key, index)) if key == selected => (index + 1) % urls.len(),
+                _ => 0,
+            };
+            self.last_opened_link = Some((selected, index));
+
+            let _ = open::that_detached(urls[index]);
+        }
+    }
+
+    /// Reads the crossterm events and updates the state of [`App`].
+    ///
+    /// If your application needs to perform work in between handling events, you can use the
+    /// [`event::poll`] function to check if there are any events available with a timeout.
+    fn handle_crossterm_events(&mut self, event: crossterm::event::Event) -> Result<()> {
+        match event {
+            // it's important to check KeyEventKind::Press to avoid handling key release events
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                self.on_key_event(key);
+                self.touch_selected_workspace();
+            }
+            Event::Mouse(mouse) => {
+                self.on_mouse_event(mouse);
+                self.touch_selected_workspace();
+            }
+            // Nothing to do, returning wakes the main loop which redraws at
+            // the new size
+            Event::Resize(_, _) => {}
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Left clicks switch to the pane clicked in and select the item under
+    /// the pointer. They are ignored while typing or while a popup is open.
+    fn on_mouse_event(&mut self, mouse: MouseEvent) {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return;
+        }
+        let busy = self.confirmation.is_some()
+            || self.picker.is_some()
+            || self.date_editing.is_some()
+            || self.tags_editing.is_some()
+            || self.command_mode
+            || self.show_help
+            || self.new_editing_id.is_some()
+            || self.search_mode
+            || self.filter_mode
+            || !matches!(self.sorting, SortingItem::None);
+        if busy {
+            return;
+        }
+        self.status_message = None;
+        self.pending_key = None;
+
+        let position = Position::new(mouse.column, mouse.row);
+        // The row's index among the listed items, if it's on one
+        let index = |area: PaneArea| {
+            area.list
+                .contains(position)
+                .then(|| area.offset + (mouse.row - area.list.y) as usize)
+        };
+        if self.workspaces_area.pane.contains(position) {
+            self.active_screen = Screen::Workspaces;
+            let clicked = index(self.workspaces_area)
+                .and_then(|index| self.slot_tree_state.ws_tree.get(index))
+                .map(|w| w.key);
+            if clicked.is_some() && clicked != self.slot_tree_state.selected_workspace {
+                self.slot_tree_state.selected_workspace = clicked;
+                self.slot_tree_state.selected_todo = None;
+                self.clear_multi_selection_when_workspace_changes();
+                self.slot_tree_state.invalidate();
+            }
+        } else if self.todos_area.pane.contains(position) {
+            self.active_screen = Screen::Todos;
+            if let Some(t) =
+                index(self.todos_area).and_then(|index| self.slot_tree_state.todo_tree.get(index))
+            {
+                self.slot_tree_state.selected_todo = Some(t.key);
+            }
+        }
+    }
+
+    /// Handles the key events and updates the state of [`App`].
+    fn on_key_event(&mut self, key: KeyEvent) {
+        self.status_message = None;
+
+        if self.confirmation.is_some() {
+            self.handle_confirmation_key_event(key);
+            return;
+        }
+
+        if self.picker.is_some() {
+            self.handle_picker_key_event(key);
+            return;
+        }
+
+        if let Some((todo_key, field)) = self.date_editing {
+            self.handle_date_key_event(todo_key, field, key);
+            return;
+        }
+
+        if let Some(todo_key) = self.tags_editing {
+            self.handle_tags_key_event(todo_key, key);
+            return;
+        }
+
+        if self.command_mode {
+            self.handle_command_key_event(key);
+            return;
+        }
+
+        let typing = self.new_editing_id.is_some()
+            || self.search_mode
+            || self.filter_mode
+            || !matches!(self.sorting, SortingItem::None);
+        // The second key of a sequence like `gJ` isn't rebound
+        let key = if typing || self.pending_key.is_some() {
+            key
+        } else {
+            let pane = match self.active_screen {
+                Screen::Workspaces => Pane::Workspaces,
+                Screen::Todos => Pane::Todos,
+            };
+            match self.keymap.resolve(key, pane) {
+                Some(key) => key,
+                None => return,
+            }
+        };
+
+        if self.show_help {
+            if matches!(key.code, KeyCode::Esc | KeyCode::Char('?')) {
+                self.show_help = false;
+            }
+            return;
+        }
+        if key.code == KeyCode::Char('?') && !typing {
+            self.show_help = true;
+            return;
+        }
+        if key.code == KeyCode::Char(':') && !typing {
+            self.command_mode = true;
+            self.command_str.clear();
+            self.pending_key = None;
+            return;
+        }
+        if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('e') && !typing {
+            self.export_store_markdown();
+            return;
+        }
+
+        match self.active_screen {
+            Screen::Workspaces => {
+                self.handle_workspace_key_event(key);
+            }
+            Screen::Todos => {
+                self.handle_todos_key_event(key);
+            }
+        }
+    }
+
+    fn handle_date_key_event(&mut self, todo_key: DefaultKey, field: DateField, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.date_editing = None,
+            KeyCode::Enter => {
+                self.date_editing = None;
+                let value = self.input.value().trim();
+                let date = if value.is_empty() {
+                    None
+                } else {
+                    match dates::parse_due(value) {
+                        Ok(date) => Some(date),
+                        Err(message) => {
+                            self.status_message = Some(message);
+                            return;
+                        }
+                    }
+                };
+                let todo = self.slot_map_store.todos_map.get_mut(todo_key).unwrap();
+                match field {
+                    DateField::Due => todo.due = date,
+                    DateField::Snooze => todo.snooze_until = date,
+                }
+                self.slot_tree_state.invalidate();
+            }
+            _ => {
+                self.input.handle_event(&crossterm::event::Event::Key(key));
+            }
+        }
+    }
+
+    fn handle_tags_key_event(&mut self, todo_key: DefaultKey, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.tags_editing = None,
+            KeyCode::Enter => {
+                self.tags_editing = None;
+                let tags = parse_tags(self.input.value());
+                let todo = self.slot_map_store.todos_map.get_mut(todo_key).unwrap();
+                todo.tags = tags;
+            }
+            _ => {
+                self.input.handle_event(&crossterm::event::Event::Key(key));
+            }
+        }
+    }
+
+    fn handle_command_key_event(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) => self.command_str.push(c),
+            KeyCode::Backspace if self.command_str.pop().is_none() => self.command_mode = false,
+            KeyCode::Esc => self.command_mode = false,
+            KeyCode::Enter => {
+                self.command_mode = false;
+                let command = std::mem::take(&mut self.command_str);
+                self.run_command(&command);
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs a command typed after `:`.
+    fn run_command(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+
+        if let Some(args) = command.strip_prefix("%s") {
+            self.substitute(args);
+            return;
+        }
+
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        match name {
+            "q" | "quit" => self.quit(),
+            "edit" => self.edit_requested = true,
+            "compact" => self.compact = !self.compact,
+            "w" | "write" => self.save(),
+            "export" => self.export_workspaces(args.trim(), false),
+            "import" => self.import(args.trim()),
+            "import-md" => self.import_markdown(args.trim()),
+            "import-tw" => self.import_taskwarrior(args.trim()),
+            "to-workspace" => self.convert_todo_to_workspace(),
+            "export-md" => self.export_workspaces(args.trim(), true),
+            "save-template" => self.save_template(args.trim()),
+            "new-from-template" => self.new_from_template(args.trim()),
+            // Hidden unless DONEIT_DEBUG is set, it's only meant for bug reports
+            "dump" if std::env::var_os("DONEIT_DEBUG").is_some() => {
+                let path = match args.trim() {
+                    "" => "doneit-debug.json",
+                    path => path,
+                };
+                self.dump_debug_state(path);
+            }
+            _ => self.status_message = Some(format!("Unknown command: {}", command)),
+        }
+    }
+
+    /// Writes the store to the data file right away instead of waiting for the
+    /// save on quit.
+    fn save(&mut self) {
+        if self.writes_blocked() {
+            return;
+        }
+        let result = self.write_data_file();
+        self.status_message = Some(match result {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(err) => format!("Could not save: {}", err),
+        });
+    }
+
+    /// Saves with the configured storage backend and returns the path of the
+    /// data file.
+    fn write_data_file(&self) -> std::io::Result<std::path::PathBuf> {
+        let backend = config::get_backend(&self.config)?;
+        backend.save(&self.get_store())?;
+        Ok(backend.path().to_path_buf())
+    }
+
+    /// Writes the store to the data file every `autosave_secs`, so a crash
+    /// only loses the last few edits. Only failures are shown.
+    fn autosave(&mut self) {
+        let interval = match self.config.autosave_secs {
+            Some(0) => return,
+            Some(secs) => Duration::from_secs(secs),
+            None => DEFAULT_AUTOSAVE_INTERVAL,
+        };
+        if self.safe_mode || self.last_autosave.elapsed() < interval {
+            return;
+        }
+        self.last_autosave = Instant::now();
+        if let Err(err) = self.write_data_file() {
+            self.status_message = Some(format!("Could not autosave: {}", err));
+        }
+    }
+
+    /// Saves the selected workspace as a template, named after it unless a
+    /// name is given.
+    fn save_template(&mut self, name: &str) {
+        if self.writes_blocked() {
+            return;
+        }
+        let Some(selected) = self.slot_tree_state.selected_workspace else {
+            self.status_message = Some("No workspace to save as a template".to_string());
+            return;
+        };
+        let workspace = self.slot_map_store.create_workspace(selected);
+        let name = match name {
+            "" => workspace.description.clone(),
+            name => name.to_string(),
+        };
+
+        let result = config::get_templates_file_path().and_then(|path| {
+            let mut templates = store::load_templates(&path)?;
+            templates.insert(name.clone(), workspace);
+            store::save_templates(&path, &templates)
+        });
+        self.status_message = Some(match result {
+            Ok(()) => format!("Saved template {}", name),
+            Err(err) => format!("Could not save template: {}", err),
+        });
+    }
+
+    /// Adds a fresh copy of a template as a child of the selected workspace,
+    /// or at the top level when none is selected. The copy gets new ids and
+    /// all of its todos start out pending.
+    fn new_from_template(&mut self, name: &str) {
+        let templates =
+            match config::get_templates_file_path().and_then(|path| store::load_templates(&path)) {
+                Ok(templates) => templates,
+                Err(err) => {
+                    self.status_message = Some(format!("Could not read templates: {}", err));
+                    return;
+                }
+            };
+        let Some(template) = templates.get(name) else {
+            let names: Vec<&str> = templates.keys().map(|k| k.as_str()).collect();
+            self.status_message = Some(match (name, names.is_empty()) {
+                (_, true) => "No templates saved yet, use :save-template".to_string(),
+                ("", false) => format!("Templates: {}", names.join(", ")),
+                _ => format!("No template {}, try one of: {}", name, names.join(", ")),
+            });
+            return;
+        };
+
+        let new_key = self.slot_map_store.insert_workspace(template);
+        self.assign_new_ids(new_key, true);
+
+        match self.slot_tree_state.selected_workspace {
+            Some(selected) => {
+                let parent = self
+                    .slot_map_store
+                    .workspaces_map
+                    .get_mut(selected)
+                    .unwrap();
+                parent.children.push(new_key);
+                self.slot_tree_state.ws_opened.insert(selected);
+            }
+            None => self.slot_map_store.root_workspaces.push(new_key),
+        }
+        self.slot_tree_state.selected_workspace = Some(new_key);
+        self.slot_tree_state.selected_todo = None;
+        self.clear_multi_selection_when_workspace_changes();
+        self.slot_tree_state.invalidate();
+    }
+
+    /// Gives the workspace at `key`, its child workspaces and all of their
+    /// todos new ids, reopening the todos if `reopen` is set. Returns how many
+    /// workspaces and todos there were.
+    fn assign_new_ids(&mut self, key: DefaultKey, reopen: bool) -> (usize, usize) {
+        let (mut workspace_count, mut todo_count) = (0, 0);
+        let mut workspaces = vec![key];
+        while let Some(key) = workspaces.pop() {
+            let id = store::new_id();
+            let workspace = self.slot_map_store.workspaces_map.get_mut(key).unwrap();
+            workspace.id = id;
+            workspaces.extend(&workspace.children);
+            workspace_count += 1;
+
+            let mut todos = workspace.todos.clone();
+            while let Some(todo_key) = todos.pop() {
+                let id = store::new_id();
+                let todo = self.slot_map_store.todos_map.get_mut(todo_key).unwrap();
+                todo.id = id;
+                if reopen {
+                    todo.status = Status::Todo;
+                }
+                todos.extend(&todo.children);
+                todo_count += 1;
+            }
+        }
+        (workspace_count, todo_count)
+    }
+
+    /// Adds the checklist in a Markdown file to the selected workspace's
+    /// todos.
+    fn import_markdown(&mut self, path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :import-md <file>".to_string());
+            return;
+        }
+        let Some(selected) = self.slot_tree_state.selected_workspace else {
+            self.status_message = Some("No workspace to import into".to_string());
+            return;
+        };
+        let markdown = match std::fs::read_to_string(path) {
+            Ok(markdown) => markdown,
+            Err(err) => {
+                self.status_message = Some(format!("Could not import {}: {}", path, err));
+                return;
+            }
+        };
+
+        let todos = store::parse_markdown(&markdown);
+        let count: usize = todos.iter().map(store::Todo::size).sum();
+        for todo in &todos {
+            let key = self.slot_map_store.insert_todo(todo);
+            self.record_todo("imported", key);
+            self.slot_map_store
+                .workspaces_map
+                .get_mut(selected)
+                .unwrap()
+                .todos
+                .push(key);
+        }
+        self.slot_tree_state.invalidate();
+
+        self.status_message = Some(format!(
+            "Imported {} todo{} from {}",
+            count,
+            if count == 1 { "" } else { "s" },
+            path
+        ));
+    }
+
+    /// Adds the workspaces of another data file under the selected workspace,
+    /// or at the root when none is selected. They get new ids so they can't
+    /// clash with the ones already here.
+    fn import(&mut self, path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :import <file>".to_string());
+            return;
+        }
+        match Store::from_json_file(&std::path::PathBuf::from(path)) {
+            Ok(store) => self.import_store(store, path),
+            Err(err) => self.status_message = Some(format!("Could not import {}: {}", path, err)),
+        }
+    }
+
+    /// Imports the output of Taskwarrior's `task export` like `:import`.
+    fn import_taskwarrior(&mut self, path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :import-tw <file>".to_string());
+            return;
+        }
+        let result = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| import::from_taskwarrior(&json, self.theme.max_urgency()));
+        match result {
+            Ok(store) => self.import_store(store, path),
+            Err(err) => self.status_message = Some(format!("Could not import {}: {}", path, err)),
+        }
+    }
+
+    /// Adds the workspaces of `store`, read from `path`, under the selected
+    /// workspace with fresh ids.
+    fn import_store(&mut self, store: Store, path: &str) {
+        let parent = self.slot_tree_state.selected_workspace;
+        let (mut workspace_count, mut todo_count) = (0, 0);
+        for workspace in &store.workspaces {
+            let key = self.slot_map_store.insert_workspace(workspace);
+            let (workspaces, todos) = self.assign_new_ids(key, false);
+            workspace_count += workspaces;
+            todo_count += todos;
+            match parent {
+                Some(parent) => self
+                    .slot_map_store
+                    .workspaces_map
+                    .get_mut(parent)
+                    .unwrap()
+                    .children
+                    .push(key),
+                None => self.slot_map_store.root_workspaces.push(key),
+            }
+        }
+        if let Some(parent) = parent {
+            self.slot_tree_state.ws_opened.insert(parent);
+        }
+        self.slot_tree_state.invalidate();
+
+        self.status_message = Some(format!(
+            "Imported {} workspace{} and {} todo{} from {}",
+            workspace_count,
+            if workspace_count == 1 { "" } else { "s" },
+            todo_count,
+            if todo_count == 1 { "" } else { "s" },
+            path
+        ));
+    }
+
+    /// Writes the in-memory tree, slotmap keys included, along with the
+    /// expansion and selection state to `path`, for attaching to bug reports.
+    fn dump_debug_state(&mut self, path: &str) {
+        if self.writes_blocked() {
+            return;
+        }
+        let workspaces: serde_json::Map<String, serde_json::Value> = self
+            .slot_map_store
+            .workspaces_map
+            .iter()
+            .map(|(k, w)| {
+                let value = serde_json::json!({
+                    "id": w.id,
+                    "description": w.description,
+                    "color": w.color,
+                    "todos": debug_keys(&w.todos),
+                    "children": debug_keys(&w.children),
+                });
+                (debug_key(&k), value)
+            })
+            .collect();
+        let todos: serde_json::Map<String, serde_json::Value> = self
+            .slot_map_store
+            .todos_map
+            .iter()
+            .map(|(k, t)| {
+                let value = serde_json::json!({
+                    "id": t.id,
+                    "description": t.description,
+                    "due": t.due,
+                    "effort": t.effort,
+                    "urgency": t.urgency,
+                    "status": t.status,
+                    "pinned": t.pinned,
+                    "snooze_until": t.snooze_until,
+                    "children": debug_keys(&t.children),
+                });
+                (debug_key(&k), value)
+            })
+            .collect();
+
+        let state = &self.slot_tree_state;
+        let dump = serde_json::json!({
+            "root_workspaces": debug_keys(&self.slot_map_store.root_workspaces),
+            "workspaces": workspaces,
+            "todos": todos,
+            "ws_opened": debug_keys(&state.ws_opened),
+            "todo_opened": debug_keys(&state.todo_opened),
+            "selected_workspace": state.selected_workspace.as_ref().map(debug_key),
+            "selected_todo": state.selected_todo.as_ref().map(debug_key),
+            "multi_selected_workspaces": debug_keys(&state.multi_selected_workspaces),
+            "multi_selected_todos": debug_keys(&state.multi_selected_todos),
+            "active_screen": match self.active_screen {
+                Screen::Workspaces => "workspaces",
+                Screen::Todos => "todos",
+            },
+        });
+
+        let result = serde_json::to_string_pretty(&dump)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(path, json));
+        self.status_message = Some(match result {
+            Ok(()) => format!("Wrote debug state to {}", path),
+            Err(err) => format!("Could not write debug state: {}", err),
+        });
+    }
+
+    /// Writes every workspace as Markdown to a `.md` file next to the data
+    /// file, e.g. `doneit.md`.
+    fn export_store_markdown(&mut self) {
+        if self.writes_blocked() {
+            return;
+        }
+        let result = config::get_data_file_path().and_then(|path| {
+            let path = path.with_extension("md");
+            std::fs::write(&path, self.get_store().to_markdown())?;
+            Ok(path)
+        });
+        self.status_message = Some(match result {
+            Ok(path) => format!("Exported to {}", path.display()),
+            Err(err) => format!("Could not export: {}", err),
+        });
+    }
+
+    /// Writes each multi-selected workspace, or the selected one when nothing
+    /// is marked, to its own file in `dir`, named after its description. JSON
+    /// files are complete data files holding just that workspace.
+    fn export_workspaces(&mut self, dir: &str, markdown: bool) {
+        if self.writes_blocked() {
+            return;
+        }
+        if dir.is_empty() {
+            self.status_message = Some("Usage: export <directory>".to_string());
+            return;
+        }
+
+        let keys: Vec<DefaultKey> = if self.slot_tree_state.multi_selected_workspaces.is_empty() {
+            self.slot_tree_state
+                .selected_workspace
+                .into_iter()
+                .collect()
+        } else {
+            // In tree order, so clashing names are numbered predictably
+            self.slot_tree_state
+                .ws_tree
+                .iter()
+                .map(|w| w.key)
+                .filter(|key| self.slot_tree_state.multi_selected_workspaces.contains(key))
+                .collect()
+        };
+        if keys.is_empty() {
+            self.status_message = Some("No workspace to export".to_string());
+            return;
+        }
+
+        let dir = std::path::PathBuf::from(dir);
+        let result = std::fs::create_dir_all(&dir).and_then(|_| {
+            let mut used_names = HashSet::new();
+            for key in &keys {
+                let workspace = self.slot_map_store.create_workspace(*key);
+                let extension = if markdown { "md" } else { "json" };
+                let stem = file_stem(&workspace.description);
+                let mut name = format!("{}.{}", stem, extension);
+                let mut n = 2;
+                while !used_names.insert(name.clone()) {
+                    name = format!("{}-{}.{}", stem, n, extension);
+                    n += 1;
+                }
+
+                let path = dir.join(name);
+                if markdown {
+                    std::fs::write(path, workspace.to_markdown())?;
+                } else {
+                    Store {
+                        version: store::STORE_VERSION,
+                        workspaces: vec![workspace],
+                    }
+                    .to_json_file(&path)?;
+                }
+            }
+            Ok(())
+        });
+
+        self.status_message = Some(match result {
+            Ok(()) => format!(
+                "Exported {} workspace{} to {}",
+                keys.len(),
+                if keys.len() == 1 { "" } else { "s" },
+                dir.display()
+            ),
+            Err(err) => format!("Export failed: {}", err),
+        });
+    }
 
-                        // Select the todo if it's in the tree
-                        if let Some(_) = self
-                            .slot_tree_state
-                            .todo_tree
-                            .iter()
-                            .find(|t| t.key == self.search_matches[self.current_match_index])
-                        {
-                            self.slot_tree_state.selected_todo =
-                                Some(self.search_matches[self.current_match_index]);
-                        }
-                    }
+    /// Replaces a substring across todo descriptions, e.g. `/old/new/`. The
+    /// first character is the delimiter. Flags after the last delimiter: `g`
+    /// to include every workspace instead of just the selected one, `c` to
+    /// confirm each replacement.
+    fn substitute(&mut self, args: &str) {
+        let Some(delimiter) = args.chars().next() else {
+            self.status_message = Some("Usage: %s/old/new/[gc]".to_string());
+            return;
+        };
+
+        let mut parts = args[delimiter.len_utf8()..].splitn(3, delimiter);
+        let from = parts.next().unwrap_or_default();
+        let to = parts.next().unwrap_or_default();
+        let flags = parts.next().unwrap_or_default();
+
+        if from.is_empty() {
+            self.status_message = Some("Usage: %s/old/new/[gc]".to_string());
+            return;
+        }
+        if let Some(flag) = flags.chars().find(|c| !matches!(c, 'g' | 'c')) {
+            self.status_message = Some(format!("Unknown flag: {}", flag));
+            return;
+        }
+
+        let workspaces = if flags.contains('g') {
+            let mut workspaces = Vec::new();
+            let mut stack: Vec<DefaultKey> = self
+                .slot_map_store
+                .root_workspaces
+                .iter()
+                .rev()
+                .copied()
+                .collect();
+            while let Some(key) = stack.pop() {
+                workspaces.push(key);
+                let workspace = self.slot_map_store.workspaces_map.get(key).unwrap();
+                stack.extend(workspace.children.iter().rev());
+            }
+            workspaces
+        } else {
+            self.slot_tree_state
+                .selected_workspace
+                .into_iter()
+                .collect()
+        };
+
+        let mut candidates = Vec::new();
+        for workspace_key in workspaces {
+            let workspace = self
+                .slot_map_store
+                .workspaces_map
+                .get(workspace_key)
+                .unwrap();
+            let mut stack: Vec<DefaultKey> = workspace.todos.iter().rev().copied().collect();
+            while let Some(key) = stack.pop() {
+                let todo = self.slot_map_store.todos_map.get(key).unwrap();
+                if todo.description.contains(from) {
+                    candidates.push((workspace_key, key));
                 }
-                _ => {}
-            },
+                stack.extend(todo.children.iter().rev());
+            }
+        }
+
+        if candidates.is_empty() {
+            self.status_message = Some(format!("Pattern not found: {}", from));
+            return;
+        }
+
+        if flags.contains('c') {
+            self.confirmation = Some(Confirmation::Replace {
+                from: from.to_string(),
+                to: to.to_string(),
+                remaining: candidates,
+                replaced: 0,
+            });
+            self.reveal_next_replacement();
+        } else {
+            for (_, key) in &candidates {
+                self.replace_in_todo(*key, from, to);
+            }
+            self.status_message = Some(replaced_message(candidates.len()));
         }
     }
 
-    /// Reads the crossterm events and updates the state of [`App`].
-    ///
-    /// If your application needs to perform work in between handling events, you can use the
-    /// [`event::poll`] function to check if there are any events available with a timeout.
-    fn handle_crossterm_events(&mut self, event: crossterm::event::Event) -> Result<()> {
-        match event {
-            // it's important to check KeyEventKind::Press to avoid handling key release events
-            Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key),
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
+    fn replace_in_todo(&mut self, key: DefaultKey, from: &str, to: &str) {
+        let todo = self.slot_map_store.todos_map.get_mut(key).unwrap();
+        todo.description = todo.description.replace(from, to);
+    }
+
+    fn handle_confirmation_key_event(&mut self, key: KeyEvent) {
+        match self.confirmation.take() {
+            Some(Confirmation::Replace {
+                from,
+                to,
+                remaining,
+                replaced,
+            }) => self.confirm_replacement(key, from, to, remaining, replaced),
+            Some(Confirmation::CutTodos) if key.code == KeyCode::Char('y') => {
+                self.cut_multi_selected_todos();
+            }
+            Some(Confirmation::CutWorkspaces) if key.code == KeyCode::Char('y') => {
+                self.cut_multi_selected_workspaces();
+            }
+            Some(Confirmation::CutTodo(todo)) if key.code == KeyCode::Char('y') => {
+                self.cut_todo(todo);
+            }
+            Some(Confirmation::CutWorkspace(workspace)) if key.code == KeyCode::Char('y') => {
+                self.cut_workspace(workspace);
+            }
             _ => {}
         }
-        Ok(())
     }
 
-    /// Handles the key events and updates the state of [`App`].
-    fn on_key_event(&mut self, key: KeyEvent) {
-        match self.active_screen {
-            Screen::Workspaces => {
-                self.handle_workspace_key_event(key);
+    fn confirm_replacement(
+        &mut self,
+        key: KeyEvent,
+        from: String,
+        to: String,
+        mut remaining: Vec<(DefaultKey, DefaultKey)>,
+        mut replaced: usize,
+    ) {
+        match key.code {
+            KeyCode::Char('y') => {
+                let (_, todo_key) = remaining.remove(0);
+                self.replace_in_todo(todo_key, &from, &to);
+                replaced += 1;
             }
-            Screen::Todos => {
-                self.handle_todos_key_event(key);
+            KeyCode::Char('n') => {
+                remaining.remove(0);
+            }
+            KeyCode::Char('a') => {
+                for (_, todo_key) in remaining.drain(..) {
+                    self.replace_in_todo(todo_key, &from, &to);
+                    replaced += 1;
+                }
             }
+            KeyCode::Char('q') | KeyCode::Esc => remaining.clear(),
+            _ => {}
+        }
+
+        if remaining.is_empty() {
+            self.status_message = Some(replaced_message(replaced));
+        } else {
+            self.confirmation = Some(Confirmation::Replace {
+                from,
+                to,
+                remaining,
+                replaced,
+            });
+            self.reveal_next_replacement();
+        }
+    }
+
+    /// Switches to the workspace of the todo about to be confirmed and
+    /// expands its ancestors so it can be selected.
+    fn reveal_next_replacement(&mut self) {
+        let Some(Confirmation::Replace { remaining, .. }) = &self.confirmation else {
+            return;
+        };
+        let Some(&(workspace_key, todo_key)) = remaining.first() else {
+            return;
+        };
+
+        if self.slot_tree_state.selected_workspace != Some(workspace_key) {
+            self.slot_tree_state.selected_workspace = Some(workspace_key);
+            self.clear_multi_selection_when_workspace_changes();
+        }
+
+        let workspace = self
+            .slot_map_store
+            .workspaces_map
+            .get(workspace_key)
+            .unwrap();
+        if let Some(path) =
+            find_todo_path(&self.slot_map_store.todos_map, &workspace.todos, todo_key)
+        {
+            self.slot_tree_state.todo_opened.extend(path);
         }
+
+        self.slot_tree_state
+            .update_workspace_tree_state(&self.slot_map_store);
+        self.slot_tree_state.selected_todo = Some(todo_key);
+        self.active_screen = Screen::Todos;
     }
 
     /// Set running to false to quit the application.
@@ -1507,9 +4760,32 @@ impl App {
         self.running = false;
     }
 
+    /// Whether `key` is one of the `quit_keys`. `:q` quits regardless.
+    fn is_quit_key(&self, key: KeyEvent) -> bool {
+        match &self.config.quit_keys {
+            Some(keys) => keys.iter().any(|spec| key_matches(spec, key)),
+            None => DEFAULT_QUIT_KEYS.iter().any(|spec| key_matches(spec, key)),
+        }
+    }
+
     // FIXME: YOU can use references here for tree. Perfomance
 
     // Multi-selection helper methods
+    /// `x` on a single todo: moves it and its children to the clipboard.
+    fn cut_todo(&mut self, key: DefaultKey) {
+        self.clipboard_todos.clear();
+        self.clipboard_todos.push(key);
+        self.delete_todo(key);
+    }
+
+    /// `x` on a single workspace: moves it and everything in it to the
+    /// clipboard.
+    fn cut_workspace(&mut self, key: DefaultKey) {
+        self.clipboard_workspaces.clear();
+        self.clipboard_workspaces = vec![key];
+        self.delete_workspace(key);
+    }
+
     fn cut_multi_selected_todos(&mut self) {
         if self.slot_tree_state.multi_selected_todos.is_empty() {
             return;
@@ -1721,11 +4997,196 @@ impl App {
     }
 }
 
-fn get_crossterm_events(tx: mpsc::Sender<crossterm::event::Event>) -> Result<()> {
+/// `ratatui::init`, reporting mouse clicks unless `mouse` is off.
+pub fn init_terminal(mouse: bool) -> DefaultTerminal {
+    let terminal = ratatui::init();
+    if mouse {
+        // Clicking is only a shortcut, so the keys still work without it
+        let _ = crossterm::execute!(std::io::stdout(), EnableMouseCapture);
+    }
+    terminal
+}
+
+/// Undoes `init_terminal`.
+pub fn restore_terminal() {
+    let _ = crossterm::execute!(std::io::stdout(), DisableMouseCapture);
+    ratatui::restore();
+}
+
+fn get_crossterm_events(
+    tx: mpsc::Sender<crossterm::event::Event>,
+    paused: Arc<AtomicBool>,
+) -> Result<()> {
     loop {
-        let event = event::read()?;
-        tx.send(event).unwrap();
+        if paused.load(Ordering::SeqCst) {
+            std::thread::sleep(EVENT_POLL_INTERVAL);
+        } else if event::poll(EVENT_POLL_INTERVAL)? {
+            tx.send(event::read()?).unwrap();
+        }
+    }
+}
+
+/// Recursively searches a todo and its children, returning whether anything in
+/// the subtree matched.
+fn search_todos(
+    todos_map: &SlotMap<DefaultKey, TodoItem>,
+    todo_key: DefaultKey,
+    query: &Query,
+    now: SystemTime,
+    matches: &mut Vec<DefaultKey>,
+    todos_containing_matches: &mut Vec<DefaultKey>,
+) -> bool {
+    let todo = todos_map.get(todo_key).unwrap();
+
+    let mut contains_match = false;
+
+    if query.matches(todo, now) {
+        matches.push(todo_key);
+        contains_match = true;
+    }
+
+    // Search in children
+    for child_key in &todo.children {
+        if search_todos(
+            todos_map,
+            *child_key,
+            query,
+            now,
+            matches,
+            todos_containing_matches,
+        ) {
+            contains_match = true;
+        }
+    }
+
+    if contains_match {
+        todos_containing_matches.push(todo_key);
+    }
+
+    contains_match
+}
+
+/// Returns the ancestors of `target` among `roots` and their descendants,
+/// outermost first.
+fn find_todo_path(
+    todos_map: &SlotMap<DefaultKey, TodoItem>,
+    roots: &[DefaultKey],
+    target: DefaultKey,
+) -> Option<Vec<DefaultKey>> {
+    for &key in roots {
+        if key == target {
+            return Some(Vec::new());
+        }
+        let todo = todos_map.get(key).unwrap();
+        if let Some(mut path) = find_todo_path(todos_map, &todo.children, target) {
+            path.insert(0, key);
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Warning for the footer when loading had to replace duplicate ids.
+fn duplicate_ids_message(reassigned: usize) -> Option<String> {
+    (reassigned > 0).then(|| {
+        format!(
+            "Found {} duplicate id{} in the data file, gave them new ones",
+            reassigned,
+            if reassigned == 1 { "" } else { "s" }
+        )
+    })
+}
+
+/// Moves `key` to the front of `siblings`, or to the back unless `first`.
+fn move_to_end(siblings: &mut Vec<DefaultKey>, key: DefaultKey, first: bool) {
+    siblings.retain(|k| *k != key);
+    if first {
+        siblings.insert(0, key);
+    } else {
+        siblings.push(key);
+    }
+}
+
+/// Returns `keys` with the pinned todos moved to the front, otherwise keeping
+/// their order.
+fn pinned_first(todos_map: &SlotMap<DefaultKey, TodoItem>, keys: &[DefaultKey]) -> Vec<DefaultKey> {
+    let (mut pinned, unpinned): (Vec<DefaultKey>, Vec<DefaultKey>) = keys
+        .iter()
+        .partition(|key| todos_map.get(**key).unwrap().pinned);
+    pinned.extend(unpinned);
+    pinned
+}
+
+fn debug_key(key: &DefaultKey) -> String {
+    format!("{:?}", key)
+}
+
+fn debug_keys<'a>(keys: impl IntoIterator<Item = &'a DefaultKey>) -> Vec<String> {
+    keys.into_iter().map(debug_key).collect()
+}
+
+/// A file name safe on any platform for a workspace description.
+fn file_stem(description: &str) -> String {
+    let stem: String = description
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "workspace".to_string()
+    } else {
+        stem
+    }
+}
+
+fn replaced_message(count: usize) -> String {
+    match count {
+        1 => "Replaced in 1 todo".to_string(),
+        n => format!("Replaced in {} todos", n),
+    }
+}
+
+/// Tags typed into the footer, split on whitespace with a leading `#`
+/// dropped and repeats left out.
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split_whitespace() {
+        let tag = tag.trim_start_matches('#');
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
     }
+    tags
+}
+
+/// One of the workspace accent colors, always the same for a tag.
+fn tag_color(tag: &str) -> Color {
+    let hash = tag.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    WORKSPACE_COLORS[hash % WORKSPACE_COLORS.len()]
+        .parse()
+        .unwrap_or(Color::Blue)
+}
+
+fn workspace_accent(workspace: &WorkspaceItem) -> Option<Color> {
+    workspace
+        .color
+        .as_ref()
+        .and_then(|color| color.parse().ok())
+}
+
+fn find_urls(text: &str) -> Vec<&str> {
+    text.split_whitespace()
+        .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(|word| word.trim_end_matches(|c: char| ",.;:!?)]}>\"'".contains(c)))
+        .collect()
 }
 
 #[derive(Default)]
@@ -1733,10 +5194,34 @@ struct ActiveTree {
     key: DefaultKey,
     parent: Option<DefaultKey>,
     depth: usize,
+    /// Whether each ancestor, outermost first, and then the item itself is
+    /// the last of its siblings. Decides where tree guides continue.
+    last_child: Vec<bool>,
+}
+
+/// Done and total todos, and their effort.
+#[derive(Default, Clone, Copy)]
+struct TodoCounts {
+    done: usize,
+    total: usize,
+    done_effort: usize,
+    total_effort: usize,
+}
+
+impl TodoCounts {
+    fn add(&mut self, other: TodoCounts) {
+        self.done += other.done;
+        self.total += other.total;
+        self.done_effort += other.done_effort;
+        self.total_effort += other.total_effort;
+    }
 }
 
 #[derive(Default)]
 struct SlotTreeState {
+    /// Set when the store or expansion state changed and `ws_tree`/`todo_tree`
+    /// need rebuilding before the next draw.
+    pub dirty: bool,
     pub selected_todo: Option<DefaultKey>,
     pub selected_workspace: Option<DefaultKey>,
     pub ws_opened: HashSet<DefaultKey>,
@@ -1745,9 +5230,55 @@ struct SlotTreeState {
     pub todo_tree: Vec<ActiveTree>,
     pub multi_selected_todos: HashSet<DefaultKey>,
     pub multi_selected_workspaces: HashSet<DefaultKey>,
+    /// Todo zoomed into with `f`. Only it and its descendants are listed.
+    pub focused_todo: Option<DefaultKey>,
+    /// Set while a `C-f` filter is active. Only matching todos and their
+    /// ancestors, collected in `filter_visible`, are listed.
+    pub filter: Option<Query>,
+    pub filter_visible: HashSet<DefaultKey>,
+    /// Toggled with `Z` to list snoozed todos anyway.
+    pub show_snoozed: bool,
+    /// Toggled with `C` to leave done todos out of `todo_tree`.
+    pub hide_completed: bool,
+    /// Snoozed todos of the selected workspace left out of `todo_tree`.
+    pub snoozed: HashSet<DefaultKey>,
+    /// When the earliest snooze ends, so a tick can bring the todo back.
+    pub next_unsnooze: Option<SystemTime>,
+    /// Mirrors the `auto_collapse_done` setting.
+    pub auto_collapse_done: bool,
+    /// Completed parents already collapsed once, so expanding them again
+    /// with `l` sticks until they are reopened.
+    pub auto_collapsed: HashSet<DefaultKey>,
+    /// Counts of each workspace's todos, nested ones included, counted with
+    /// the trees so drawing doesn't walk every subtree on each frame.
+    pub own_counts: HashMap<DefaultKey, TodoCounts>,
+    /// Likewise, with the todos of child workspaces added in.
+    pub subtree_counts: HashMap<DefaultKey, TodoCounts>,
+    /// Due dates of every pending todo, for the overdue count in the footer.
+    pub pending_due: Vec<SystemTime>,
+    /// Effort of the pending todos in `todo_tree`.
+    pub remaining_effort: usize,
 }
 
 impl SlotTreeState {
+    /// Tree state for a freshly loaded store, with the todos that were left
+    /// expanded opened again.
+    fn new(store: &Store, slot_map_store: &SlotMapStore, config: &Config) -> Self {
+        let opened = store.opened_todo_ids();
+        let mut state = SlotTreeState {
+            todo_opened: slot_map_store
+                .todos_map
+                .iter()
+                .filter(|(_, todo)| opened.contains(todo.id.as_str()))
+                .map(|(key, _)| key)
+                .collect(),
+            auto_collapse_done: config.auto_collapse_done.unwrap_or(false),
+            ..SlotTreeState::default()
+        };
+        state.update_workspace_tree_state(slot_map_store);
+        state
+    }
+
     fn add_workspace_to_tree(
         &self,
         ws_tree: &mut Vec<ActiveTree>,
@@ -1755,19 +5286,29 @@ impl SlotTreeState {
         key: DefaultKey,
         depth: usize,
         parent: Option<DefaultKey>,
+        is_last: bool,
     ) {
-        ws_tree.push(ActiveTree {
-            key: key,
-            parent: parent,
-            depth,
-        });
+        // Walk with an explicit stack so very deep trees can't overflow the
+        // call stack. Children are pushed in reverse to keep their order.
+        let mut stack = vec![(key, depth, parent, vec![is_last])];
+        while let Some((key, depth, parent, last_child)) = stack.pop() {
+            if self.ws_opened.contains(&key) {
+                let workspace = store.workspaces_map.get(key).unwrap();
+                let count = workspace.children.len();
+                stack.extend(workspace.children.iter().enumerate().rev().map(|(i, k)| {
+                    let mut child_last = last_child.clone();
+                    child_last.push(i + 1 == count);
+                    (*k, depth + 1, Some(key), child_last)
+                }));
+            }
 
-        if self.ws_opened.contains(&key) {
-            let workspace = store.workspaces_map.get(key).unwrap();
-            workspace.children.iter().for_each(|k| {
-                self.add_workspace_to_tree(ws_tree, store, *k, depth + 1, Some(key));
+            ws_tree.push(ActiveTree {
+                key,
+                parent,
+                depth,
+                last_child,
             });
-        };
+        }
     }
 
     fn add_todo_to_tree(
@@ -1777,36 +5318,500 @@ impl SlotTreeState {
         key: DefaultKey,
         depth: usize,
         parent: Option<DefaultKey>,
+        is_last: bool,
     ) {
-        todo_tree.push(ActiveTree {
-            key: key,
-            parent: parent,
-            depth,
-        });
+        let mut stack = vec![(key, depth, parent, vec![is_last])];
+        while let Some((key, depth, parent, last_child)) = stack.pop() {
+            // Filtering shows every match, however deep
+            if self.todo_opened.contains(&key) || self.filter.is_some() {
+                let todo = store.todos_map.get(key).unwrap();
+                let children = self.visible_todos(store, &todo.children);
+                let count = children.len();
+                stack.extend(children.into_iter().enumerate().rev().map(|(i, k)| {
+                    let mut child_last = last_child.clone();
+                    child_last.push(i + 1 == count);
+                    (k, depth + 1, Some(key), child_last)
+                }));
+            }
 
-        if self.todo_opened.contains(&key) {
-            let todo = store.todos_map.get(key).unwrap();
-            todo.children.iter().for_each(|k| {
-                self.add_todo_to_tree(todo_tree, store, *k, depth + 1, Some(key));
+            todo_tree.push(ActiveTree {
+                key,
+                parent,
+                depth,
+                last_child,
             });
         }
     }
 
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
     pub fn update_workspace_tree_state(&mut self, store: &store::SlotMapStore) {
+        self.dirty = false;
+        let previous_index = self
+            .selected_todo
+            .and_then(|selected| self.todo_tree.iter().position(|t| t.key == selected));
+
         let mut ws_tree = Vec::new();
-        store.root_workspaces.iter().for_each(|w| {
-            self.add_workspace_to_tree(&mut ws_tree, store, *w, 0, None);
+        let count = store.root_workspaces.len();
+        store.root_workspaces.iter().enumerate().for_each(|(i, w)| {
+            self.add_workspace_to_tree(&mut ws_tree, store, *w, 0, None, i + 1 == count);
         });
 
         let mut todo_tree = Vec::new();
         if let Some(selected) = self.selected_workspace {
             let workspace = store.workspaces_map.get(selected).unwrap();
-            workspace.todos.iter().for_each(|t| {
-                self.add_todo_to_tree(&mut todo_tree, store, *t, 0, None);
+            if self.auto_collapse_done {
+                self.collapse_done_todos(store, &workspace.todos);
+            }
+            self.find_snoozed_todos(store, &workspace.todos);
+            self.filter_visible.clear();
+            if let Some(query) = &self.filter {
+                let now = SystemTime::now();
+                let (mut matches, mut containing) = (Vec::new(), Vec::new());
+                for todo in &workspace.todos {
+                    search_todos(
+                        &store.todos_map,
+                        *todo,
+                        query,
+                        now,
+                        &mut matches,
+                        &mut containing,
+                    );
+                }
+                self.filter_visible.extend(containing);
+            }
+            let focus_path = self
+                .focused_todo
+                .and_then(|focused| find_todo_path(&store.todos_map, &workspace.todos, focused));
+            // The focused todo keeps its real parent and depth so handlers that
+            // look up its siblings still find them and max_depth still holds.
+            let (roots, parent, depth) = match (self.focused_todo, focus_path) {
+                (Some(focused), Some(path)) => {
+                    self.todo_opened.insert(focused);
+                    (vec![focused], path.last().copied(), path.len())
+                }
+                // Cut, deleted or in another workspace
+                _ => {
+                    self.focused_todo = None;
+                    (self.visible_todos(store, &workspace.todos), None, 0)
+                }
+            };
+            let count = roots.len();
+            roots.into_iter().enumerate().for_each(|(i, t)| {
+                self.add_todo_to_tree(&mut todo_tree, store, t, depth, parent, i + 1 == count);
             });
         }
 
+        self.remaining_effort = todo_tree
+            .iter()
+            .map(|t| &store.todos_map[t.key])
+            .filter(|todo| !todo.status.is_done())
+            .map(|todo| todo.effort)
+            .sum();
         self.ws_tree = ws_tree;
         self.todo_tree = todo_tree;
+        self.snap_selected_todo(previous_index);
+        // Marked todos that are no longer listed, hidden by `C`, a filter,
+        // snoozing or collapsing, can't be acted on either
+        let listed: HashSet<DefaultKey> = self.todo_tree.iter().map(|t| t.key).collect();
+        self.multi_selected_todos.retain(|key| listed.contains(key));
+        self.count_todos(store);
+    }
+
+    /// Fills `own_counts`, `subtree_counts` and `pending_due` in one pass
+    /// over the store.
+    fn count_todos(&mut self, store: &SlotMapStore) {
+        self.own_counts.clear();
+        self.subtree_counts.clear();
+        self.pending_due.clear();
+
+        let mut order = Vec::new();
+        let mut workspaces = store.root_workspaces.clone();
+        while let Some(key) = workspaces.pop() {
+            order.push(key);
+            let workspace = &store.workspaces_map[key];
+            workspaces.extend(&workspace.children);
+
+            let mut counts = TodoCounts::default();
+            let mut todos = workspace.todos.clone();
+            while let Some(key) = todos.pop() {
+                let todo = &store.todos_map[key];
+                counts.total += 1;
+                counts.total_effort += todo.effort;
+                if todo.status.is_done() {
+                    counts.done += 1;
+                    counts.done_effort += todo.effort;
+                } else {
+                    self.pending_due.extend(todo.due);
+                }
+                todos.extend(&todo.children);
+            }
+            self.own_counts.insert(key, counts);
+        }
+
+        // Parents come before their children, so going through it backwards
+        // settles every child before its parent
+        for key in order.into_iter().rev() {
+            let mut counts = self.own_counts[&key];
+            for child in &store.workspaces_map[key].children {
+                counts.add(self.subtree_counts[child]);
+            }
+            self.subtree_counts.insert(key, counts);
+        }
+    }
+
+    /// Fills `snoozed` with the todos under `roots` that are hidden for now,
+    /// and `next_unsnooze` with the first time one of them reappears.
+    fn find_snoozed_todos(&mut self, store: &SlotMapStore, roots: &[DefaultKey]) {
+        let now = SystemTime::now();
+        self.snoozed.clear();
+        self.next_unsnooze = None;
+        let mut stack = roots.to_vec();
+        while let Some(key) = stack.pop() {
+            let todo = store.todos_map.get(key).unwrap();
+            if let Some(until) = todo.snooze_until.filter(|until| *until > now) {
+                if !self.show_snoozed {
+                    self.snoozed.insert(key);
+                }
+                self.next_unsnooze = Some(self.next_unsnooze.map_or(until, |next| next.min(until)));
+            }
+            stack.extend(&todo.children);
+        }
+    }
+
+    /// `keys` in the order they are listed, pinned first. Snoozed todos,
+    /// hidden done ones and those filtered out are left out.
+    fn visible_todos(&self, store: &SlotMapStore, keys: &[DefaultKey]) -> Vec<DefaultKey> {
+        pinned_first(&store.todos_map, keys)
+            .into_iter()
+            .filter(|key| !self.snoozed.contains(key))
+            .filter(|key| !self.hide_completed || !store.todos_map[*key].status.is_done())
+            .filter(|key| self.filter.is_none() || self.filter_visible.contains(key))
+            .collect()
+    }
+
+    /// Closes the parents among `roots` and their descendants whose whole
+    /// subtree just became done.
+    fn collapse_done_todos(&mut self, store: &SlotMapStore, roots: &[DefaultKey]) {
+        // Parents come before their children, so going through it backwards
+        // settles every subtree before its parent
+        let mut order = Vec::new();
+        let mut stack = roots.to_vec();
+        while let Some(key) = stack.pop() {
+            order.push(key);
+            stack.extend(&store.todos_map.get(key).unwrap().children);
+        }
+
+        let mut subtree_done = HashMap::new();
+        for key in order.into_iter().rev() {
+            let todo = store.todos_map.get(key).unwrap();
+            let done =
+                todo.status.is_done() && todo.children.iter().all(|child| subtree_done[child]);
+            subtree_done.insert(key, done);
+            if todo.children.is_empty() {
+                continue;
+            }
+            if !done {
+                self.auto_collapsed.remove(&key);
+            } else if self.auto_collapsed.insert(key) {
+                self.todo_opened.remove(&key);
+            }
+        }
+    }
+
+    /// Moves the todo selection to the closest remaining row when the selected
+    /// todo is no longer visible, e.g. after a collapse or a search, so the
+    /// selection never points at a row that isn't drawn.
+    fn snap_selected_todo(&mut self, previous_index: Option<usize>) {
+        let Some(selected) = self.selected_todo else {
+            return;
+        };
+        if self.todo_tree.iter().any(|t| t.key == selected) {
+            return;
+        }
+
+        self.selected_todo = if self.todo_tree.is_empty() {
+            None
+        } else {
+            let index = previous_index.unwrap_or(0).min(self.todo_tree.len() - 1);
+            Some(self.todo_tree[index].key)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An app over a single, selected `Work` workspace holding the todos of
+    /// `outline`, in safe mode so nothing is written to disk.
+    fn app_with(outline: &str) -> App {
+        app_with_config(outline, Config::default())
+    }
+
+    fn app_with_config(outline: &str, config: Config) -> App {
+        let mut store = Store::default();
+        store
+            .workspace_by_name_mut("Work")
+            .todos
+            .extend(store::parse_outline(outline));
+        let mut app = App::new(store, Theme::default(), config).with_safe_mode(true);
+        app.slot_tree_state.selected_workspace =
+            app.slot_map_store.root_workspaces.first().copied();
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        app
+    }
+
+    fn key_of(app: &App, description: &str) -> DefaultKey {
+        app.slot_map_store
+            .todos_map
+            .iter()
+            .find(|(_, todo)| todo.description == description)
+            .map(|(key, _)| key)
+            .unwrap()
+    }
+
+    #[test]
+    fn moving_a_todo_under_its_own_child_is_refused() {
+        let mut app = app_with("parent\n  child\n    grandchild\n");
+        let parent = key_of(&app, "parent");
+        let child = key_of(&app, "child");
+        let grandchild = key_of(&app, "grandchild");
+
+        app.move_todo_under(parent, grandchild, 2);
+        assert!(app.status_message.is_some());
+        assert_eq!(app.slot_map_store.todos_map[parent].children, vec![child]);
+        assert!(app.slot_map_store.todos_map[grandchild].children.is_empty());
+
+        app.move_todo_under(parent, parent, 0);
+        assert_eq!(app.slot_map_store.todos_map[parent].children, vec![child]);
+    }
+
+    #[test]
+    fn done_subtrees_collapse_once() {
+        let config = Config {
+            auto_collapse_done: Some(true),
+            ..Config::default()
+        };
+        let mut app = app_with_config("[x] a\n  [x] b\n    [ ] c\n  [ ] d\n", config);
+        let (a, b, c) = (key_of(&app, "a"), key_of(&app, "b"), key_of(&app, "c"));
+        app.slot_tree_state.todo_opened.extend([a, b]);
+        app.slot_map_store.todos_map[c].status = Status::Done;
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        // b's subtree just became done, a still has d pending
+        assert!(app.slot_tree_state.todo_opened.contains(&a));
+        assert!(!app.slot_tree_state.todo_opened.contains(&b));
+
+        // Expanding it again by hand sticks
+        app.slot_tree_state.todo_opened.insert(b);
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        assert!(app.slot_tree_state.todo_opened.contains(&b));
+    }
+
+    #[test]
+    fn loading_a_store_drops_keys_into_the_old_one() {
+        let config = Config {
+            collapse_idle_workspaces_secs: Some(600),
+            ..Config::default()
+        };
+        let mut app = app_with_config("a\nb\n", config);
+        app.slot_tree_state.selected_todo = Some(key_of(&app, "a"));
+        app.mark_todos_for_move();
+        app.touch_selected_workspace();
+        assert!(!app.moving_todos.is_empty());
+        assert!(!app.workspace_accessed.is_empty());
+
+        app.load_store(Store::default());
+        assert!(app.moving_todos.is_empty());
+        assert!(app.workspace_accessed.is_empty());
+    }
+
+    #[test]
+    fn indenting_goes_under_the_sibling_shown_above() {
+        let mut app = app_with("a\n[x] b\nc\n");
+        let (a, b, c) = (key_of(&app, "a"), key_of(&app, "b"), key_of(&app, "c"));
+        app.slot_tree_state.hide_completed = true;
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        app.slot_tree_state.selected_todo = Some(c);
+
+        app.indent_selected_todo();
+        assert_eq!(app.slot_map_store.todos_map[a].children, vec![c]);
+        assert!(app.slot_map_store.todos_map[b].children.is_empty());
+    }
+
+    #[test]
+    fn indenting_too_deep_is_refused() {
+        let config = Config {
+            max_depth: Some(2),
+            ..Config::default()
+        };
+        let mut app = app_with_config("a\nb\n  c\n", config);
+        let (a, b) = (key_of(&app, "a"), key_of(&app, "b"));
+        app.slot_tree_state.selected_todo = Some(b);
+
+        app.indent_selected_todo();
+        assert!(app.status_message.is_some());
+        assert!(app.slot_map_store.todos_map[a].children.is_empty());
+        let workspace = app.slot_tree_state.selected_workspace.unwrap();
+        assert_eq!(
+            app.slot_map_store.workspaces_map[workspace].todos,
+            vec![a, b]
+        );
+    }
+
+    #[test]
+    fn converting_a_todo_forgets_its_key() {
+        let mut app = app_with("a\n  b\nc\n");
+        let (a, c) = (key_of(&app, "a"), key_of(&app, "c"));
+        app.clipboard_todos = vec![a, c];
+        app.slot_tree_state.selected_todo = Some(a);
+        app.last_completion = Some(Completion {
+            key: a,
+            previous: Status::Todo,
+            at: Instant::now(),
+            moved: None,
+        });
+
+        app.convert_todo_to_workspace();
+        assert!(!app.slot_map_store.todos_map.contains_key(a));
+        assert_eq!(app.clipboard_todos, vec![c]);
+        assert!(app.last_completion.is_none());
+        let workspace = app.slot_tree_state.selected_workspace.unwrap();
+        assert_eq!(
+            app.slot_map_store.workspaces_map[workspace].description,
+            "a"
+        );
+    }
+
+    #[test]
+    fn todo_counts_add_up_child_workspaces() {
+        let mut store = Store::default();
+        let work = store.workspace_by_name_mut("Work");
+        work.todos = store::parse_outline("[x] a\n  b\nc\n");
+        work.todos[0].effort = 2;
+        work.todos[1].due = dates::parse_due("2000-01-01").ok();
+        let mut notes = Store::default();
+        notes.workspace_by_name_mut("Notes").todos = store::parse_outline("[x] d\n");
+        work.children = notes.workspaces;
+        let app = App::new(store, Theme::default(), Config::default()).with_safe_mode(true);
+
+        let root = app.slot_map_store.root_workspaces[0];
+        let child = app.slot_map_store.workspaces_map[root].children[0];
+        let counts = |map: &HashMap<DefaultKey, TodoCounts>, key| {
+            let TodoCounts {
+                done,
+                total,
+                done_effort,
+                total_effort,
+            } = map[&key];
+            (done, total, done_effort, total_effort)
+        };
+        let state = &app.slot_tree_state;
+        assert_eq!(counts(&state.own_counts, root), (1, 3, 2, 2));
+        assert_eq!(counts(&state.own_counts, child), (1, 1, 0, 0));
+        assert_eq!(counts(&state.subtree_counts, root), (2, 4, 2, 2));
+        assert_eq!(state.pending_due.len(), 1);
+    }
+
+    #[test]
+    fn pasted_lines_too_deep_are_skipped_and_reported() {
+        let config = Config {
+            max_depth: Some(3),
+            ..Config::default()
+        };
+        let mut app = app_with_config("a\n", config);
+        let a = key_of(&app, "a");
+        app.slot_tree_state.selected_todo = Some(a);
+
+        app.paste_todos(&store::parse_outline("b\n  c\n    d\ne\n"));
+        assert_eq!(app.slot_map_store.todos_map.len(), 2);
+        let e = key_of(&app, "e");
+        assert_eq!(app.slot_map_store.todos_map[a].children, vec![e]);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Pasted 1 todo, skipped 1 that would nest deeper than max_depth")
+        );
+    }
+
+    #[test]
+    fn pasted_lines_too_deep_for_the_top_level_are_skipped() {
+        let config = Config {
+            max_depth: Some(2),
+            ..Config::default()
+        };
+        let mut app = app_with_config("a\n", config);
+        app.slot_tree_state.selected_todo = None;
+
+        app.paste_todos(&store::parse_outline("b\n  c\n    d\ne\n"));
+        assert_eq!(app.slot_map_store.todos_map.len(), 2);
+        let (a, e) = (key_of(&app, "a"), key_of(&app, "e"));
+        let work = app.slot_tree_state.selected_workspace.unwrap();
+        assert_eq!(app.slot_map_store.workspaces_map[work].todos, vec![a, e]);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Pasted 1 todo, skipped 1 that would nest deeper than max_depth")
+        );
+    }
+
+    #[test]
+    fn pasting_too_deep_inserts_nothing() {
+        let config = Config {
+            max_depth: Some(2),
+            ..Config::default()
+        };
+        let mut app = app_with_config("a\n  b\n", config);
+        let (a, b) = (key_of(&app, "a"), key_of(&app, "b"));
+        app.slot_tree_state.todo_opened.insert(a);
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        app.slot_tree_state.selected_todo = Some(b);
+
+        assert!(app.attach_pasted_todo(&store::Todo::new("c")).is_none());
+        assert_eq!(app.slot_map_store.todos_map.len(), 2);
+        assert!(app.slot_map_store.todos_map[b].children.is_empty());
+
+        app.slot_tree_state.selected_todo = Some(a);
+        let c = app.attach_pasted_todo(&store::Todo::new("c")).unwrap();
+        assert_eq!(app.slot_map_store.todos_map[a].children, vec![b, c]);
+    }
+
+    #[test]
+    fn hidden_todos_drop_out_of_the_multi_selection() {
+        let mut app = app_with("[x] a\nb\n");
+        let (a, b) = (key_of(&app, "a"), key_of(&app, "b"));
+        app.slot_tree_state.multi_selected_todos.extend([a, b]);
+        app.slot_tree_state.hide_completed = true;
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        assert_eq!(app.slot_tree_state.multi_selected_todos, HashSet::from([b]));
+
+        app.cut_multi_selected_todos();
+        assert_eq!(app.clipboard_todos, vec![b]);
+        assert!(app.slot_map_store.todos_map.contains_key(a));
+    }
+
+    #[test]
+    fn focusing_keeps_real_depths_for_max_depth() {
+        let config = Config {
+            max_depth: Some(2),
+            ..Config::default()
+        };
+        let mut app = app_with_config("a\n  b\n", config);
+        let b = key_of(&app, "b");
+        app.slot_tree_state.focused_todo = Some(b);
+        app.slot_tree_state
+            .update_workspace_tree_state(&app.slot_map_store);
+        app.slot_tree_state.selected_todo = Some(b);
+
+        assert_eq!(app.slot_tree_state.todo_tree[0].depth, 1);
+        assert_eq!(app.tree_prefix(&app.slot_tree_state.todo_tree[0]), "");
+        assert!(app.attach_pasted_todo(&store::Todo::new("c")).is_none());
+        assert!(app.slot_map_store.todos_map[b].children.is_empty());
     }
 }
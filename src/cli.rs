@@ -0,0 +1,330 @@
+use crate::dates;
+use crate::store::{outline_line, parse_outline, Store, Todo, Workspace};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+pub const USAGE: &str = "\
+Usage: doneit [--no-color] [--profile <name>] [--file <path>] [--safe]
+       doneit add [<todo>] [--workspace <name>] [--due <date>] [--priority <level>]
+       doneit list [<workspace>] [--pending] [--due <days>] [--priority <level>]
+
+Commands:
+  add    Add the given todo, or one todo per line read from stdin when
+         there is none. Indented lines become children of the line above.
+         The workspace defaults to Inbox and is created when missing.
+           --due <date>        e.g. tomorrow, +3d or 2024-12-31
+           --priority <level>  0 being the lowest, also --urgency
+  list   Print todos, every workspace unless one is given by name or by a
+         path such as Home/Garden. Filters:
+           --pending           not done yet
+           --due <days>        due within this many days, overdue included
+           --priority <level>  at least this priority, 0 being the lowest
+
+Options:
+  --profile <name>  Use a separate data file, doneit.<name>.json, and
+                    theme.<name>.yaml when it exists. Defaults to
+                    $DONEIT_PROFILE.
+  --file <path>     Read and save the todos in this file instead of the
+                    one in the data directory.
+  --safe            Never write to disk. Edits are lost on quit.";
+
+const DEFAULT_WORKSPACE: &str = "Inbox";
+
+pub enum Command {
+    /// Launch the TUI, the default when no command is given.
+    Interactive,
+    Add(NewTodos),
+    List(ListFilter),
+    Help,
+}
+
+/// What `add` adds, and where.
+pub struct NewTodos {
+    pub workspace: String,
+    /// The todo to add. Read from stdin when not given.
+    pub description: Option<String>,
+    pub due: Option<SystemTime>,
+    pub priority: Option<usize>,
+}
+
+impl NewTodos {
+    /// The todos to add, `description` or else the outline in `input`. The
+    /// due date and priority go to the top level ones.
+    pub fn todos(&self, input: &str) -> Vec<Todo> {
+        let mut todos = match &self.description {
+            Some(description) => vec![Todo::new(description)],
+            None => parse_outline(input),
+        };
+        for todo in &mut todos {
+            todo.due = self.due.or(todo.due);
+            todo.urgency = self.priority.unwrap_or(todo.urgency);
+        }
+        todos
+    }
+}
+
+/// Which todos `list` prints. Ancestors of a matching todo are printed too so
+/// it keeps its context.
+#[derive(Default)]
+pub struct ListFilter {
+    /// A workspace name, or a `/` separated path of names starting anywhere.
+    pub workspace: Option<String>,
+    pub pending: bool,
+    pub due_within_days: Option<u64>,
+    pub min_priority: Option<usize>,
+}
+
+impl ListFilter {
+    fn matches(&self, todo: &Todo) -> bool {
+        if self.pending && todo.status.is_done() {
+            return false;
+        }
+        if let Some(days) = self.due_within_days {
+            let deadline = SystemTime::now() + Duration::from_secs(days * 24 * 60 * 60);
+            if todo.due.is_none_or(|due| due > deadline) {
+                return false;
+            }
+        }
+        if let Some(priority) = self.min_priority {
+            if todo.urgency < priority {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub struct Args {
+    pub command: Command,
+    pub no_color: bool,
+    pub profile: Option<String>,
+    pub file: Option<PathBuf>,
+    pub safe: bool,
+}
+
+/// Parses the arguments after the program name. The error is a message meant
+/// to be shown along with [`USAGE`]. `max_urgency` looks up the highest
+/// priority of the profile's theme, to check `add --priority` against.
+pub fn parse(
+    args: impl IntoIterator<Item = String>,
+    max_urgency: impl FnOnce(Option<&str>) -> usize,
+) -> Result<Args, String> {
+    let mut parsed = Args {
+        command: Command::Interactive,
+        no_color: false,
+        profile: None,
+        file: None,
+        safe: false,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), &mut parsed.command) {
+            ("--no-color", _) => parsed.no_color = true,
+            ("--profile", _) => {
+                let name: String = value(&arg, args.next())?;
+                if name.is_empty() || name.contains(['/', '\\', '.']) {
+                    return Err(format!("Invalid profile name: {}", name));
+                }
+                parsed.profile = Some(name);
+            }
+            ("--file", _) => parsed.file = Some(value(&arg, args.next())?),
+            ("--safe", _) => parsed.safe = true,
+            ("-h" | "--help", _) => parsed.command = Command::Help,
+            ("add", Command::Interactive) => {
+                parsed.command = Command::Add(NewTodos {
+                    workspace: DEFAULT_WORKSPACE.to_string(),
+                    description: None,
+                    due: None,
+                    priority: None,
+                })
+            }
+            ("list", Command::Interactive) => parsed.command = Command::List(ListFilter::default()),
+            ("-w" | "--workspace", Command::Add(new)) => {
+                new.workspace = value(&arg, args.next())?;
+            }
+            ("--due", Command::Add(new)) => {
+                let due: String = value(&arg, args.next())?;
+                new.due = Some(dates::parse_due(&due)?);
+            }
+            ("--priority" | "--urgency", Command::Add(new)) => {
+                new.priority = Some(value(&arg, args.next())?);
+            }
+            (description, Command::Add(new))
+                if new.description.is_none() && !description.starts_with('-') =>
+            {
+                new.description = Some(description.to_string());
+            }
+            ("-w" | "--workspace", Command::List(filter)) => {
+                filter.workspace = Some(value(&arg, args.next())?);
+            }
+            ("--pending", Command::List(filter)) => filter.pending = true,
+            ("--due", Command::List(filter)) => {
+                filter.due_within_days = Some(value(&arg, args.next())?);
+            }
+            ("--priority", Command::List(filter)) => {
+                filter.min_priority = Some(value(&arg, args.next())?);
+            }
+            (path, Command::List(filter))
+                if filter.workspace.is_none() && !path.starts_with('-') =>
+            {
+                filter.workspace = Some(path.to_string());
+            }
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    if let Command::Add(NewTodos {
+        priority: Some(priority),
+        ..
+    }) = parsed.command
+    {
+        let max_urgency = max_urgency(parsed.profile.as_deref());
+        if priority > max_urgency {
+            return Err(format!(
+                "Invalid value for --priority: {}, the highest is {}",
+                priority, max_urgency
+            ));
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Parses the value following the flag `arg`.
+fn value<T: FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", arg))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", arg, value))
+}
+
+/// Renders the todos matching `filter` as indented text. Without a workspace
+/// in the filter every workspace with a match is listed under its path.
+pub fn list(store: &Store, filter: &ListFilter) -> Result<String, String> {
+    let mut text = String::new();
+
+    match &filter.workspace {
+        Some(path) => {
+            let workspace = workspace_by_path(store, path)
+                .ok_or_else(|| format!("No workspace named {}", path))?;
+            list_todos(&workspace.todos, filter, 0, &mut text);
+        }
+        None => {
+            for workspace in &store.workspaces {
+                list_workspace(workspace, "", filter, &mut text);
+            }
+        }
+    }
+
+    Ok(text)
+}
+
+/// The workspace at `path`, e.g. `Home/Garden`, whose first name may be
+/// nested anywhere while the rest are children of the one before.
+fn workspace_by_path<'a>(store: &'a Store, path: &str) -> Option<&'a Workspace> {
+    let mut names = path.split('/').map(str::trim);
+    let mut workspace = store.workspace_by_name(names.next()?)?;
+    for name in names {
+        workspace = workspace.children.iter().find(|w| w.description == name)?;
+    }
+    Some(workspace)
+}
+
+fn list_workspace(
+    workspace: &Workspace,
+    parent_path: &str,
+    filter: &ListFilter,
+    text: &mut String,
+) {
+    let path = if parent_path.is_empty() {
+        workspace.description.clone()
+    } else {
+        format!("{}/{}", parent_path, workspace.description)
+    };
+
+    let mut todos = String::new();
+    list_todos(&workspace.todos, filter, 1, &mut todos);
+    if !todos.is_empty() {
+        text.push_str(&path);
+        text.push('\n');
+        text.push_str(&todos);
+    }
+
+    for child in &workspace.children {
+        list_workspace(child, &path, filter, text);
+    }
+}
+
+/// Appends the todos in `todos` that match, or have a descendant that
+/// matches, returning whether anything was appended.
+fn list_todos(todos: &[Todo], filter: &ListFilter, depth: usize, text: &mut String) -> bool {
+    let mut listed = false;
+    for todo in todos {
+        let mut children = String::new();
+        let children_listed = list_todos(&todo.children, filter, depth + 1, &mut children);
+        if children_listed || filter.matches(todo) {
+            text.push_str(&outline_line(depth, todo.status, &todo.description));
+            text.push_str(&children);
+            listed = true;
+        }
+    }
+    listed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_add(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(|arg| arg.to_string()), |_| 3)
+    }
+
+    #[test]
+    fn add_priority_is_checked_against_the_theme() {
+        let args = parse_add(&["add", "a", "--priority", "3"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Command::Add(NewTodos {
+                priority: Some(3),
+                ..
+            })
+        ));
+        assert_eq!(
+            parse_add(&["add", "a", "--priority", "4"]).err().unwrap(),
+            "Invalid value for --priority: 4, the highest is 3"
+        );
+    }
+
+    #[test]
+    fn the_theme_is_looked_up_for_the_profile() {
+        let args = ["--profile", "work", "add", "--priority", "1", "a"].map(String::from);
+        let mut looked_up = None;
+        parse(args, |profile| {
+            looked_up = profile.map(String::from);
+            1
+        })
+        .unwrap();
+        assert_eq!(looked_up.as_deref(), Some("work"));
+    }
+
+    #[test]
+    fn listed_paths_can_be_passed_back() {
+        let mut store = Store::default();
+        let home = store.workspace_by_name_mut("Home");
+        home.children.push(Workspace {
+            id: "garden".to_string(),
+            description: "Garden".to_string(),
+            color: None,
+            children: Vec::new(),
+            todos: parse_outline("weed\n"),
+            opened_todos: Vec::new(),
+        });
+
+        let text = list(&store, &ListFilter::default()).unwrap();
+        assert_eq!(text, "Home/Garden\n  [ ] weed\n");
+        let garden = workspace_by_path(&store, text.lines().next().unwrap()).unwrap();
+        assert_eq!(garden.description, "Garden");
+    }
+}
@@ -1,7 +1,6 @@
 use std::str::FromStr;
 
-use ratatui::style::Color;
-
+use ratatui::style::{Color, Style, Stylize};
 
 pub struct Theme {
     pub text: Color,
@@ -12,8 +11,92 @@ pub struct Theme {
     pub active_highlight: Color,
     pub inactive_highlight: Color,
     pub highlight_text_secondary: Color,
-} 
 
+    /// Description and due date of pending todos past their due date.
+    pub overdue: Color,
+
+    /// Glyph and color for each priority level, lowest first.
+    pub priorities: Vec<Priority>,
+
+    /// Render without colors, for `NO_COLOR` and `--no-color`.
+    pub monochrome: bool,
+}
+
+pub struct Priority {
+    pub glyph: String,
+    pub color: Color,
+}
+
+impl Theme {
+    pub fn default_priorities() -> Vec<Priority> {
+        [
+            ("󰯬", Color::Green),
+            ("󰯯", Color::Yellow),
+            ("󰯲", Color::Magenta),
+            ("󰯵", Color::Red),
+        ]
+        .into_iter()
+        .map(|(glyph, color)| Priority {
+            glyph: glyph.to_string(),
+            color,
+        })
+        .collect()
+    }
+
+    /// Highest urgency value a todo can be raised to.
+    pub fn max_urgency(&self) -> usize {
+        self.priorities.len() - 1
+    }
+
+    /// Every style used for rendering goes through here so monochrome mode can
+    /// drop the colors while keeping modifiers such as bold and reverse video.
+    pub fn paint(&self, style: Style) -> Style {
+        if self.monochrome {
+            let mut style = style;
+            style.fg = None;
+            style.bg = None;
+            style
+        } else {
+            style
+        }
+    }
+
+    /// The item under the cursor, shown in reverse video without colors.
+    pub fn selection(&self) -> Style {
+        let style = Style::default().fg(self.text).bg(self.item_highlight);
+        self.paint(if self.monochrome {
+            style.reversed()
+        } else {
+            style
+        })
+    }
+
+    /// Multi-selected items, underlined without colors.
+    pub fn marked(&self) -> Style {
+        let style = Style::default().fg(Color::Yellow);
+        self.paint(if self.monochrome {
+            style.underlined()
+        } else {
+            style
+        })
+    }
+
+    /// Mode labels such as the footer badges and pane titles.
+    pub fn label(&self, fg: Color, bg: Color) -> Style {
+        let style = Style::default().fg(fg).bg(bg);
+        self.paint(if self.monochrome {
+            style.reversed()
+        } else {
+            style
+        })
+    }
+
+    /// Priority for the given urgency, clamped so todos saved with more levels
+    /// than are currently configured still render.
+    pub fn priority(&self, urgency: usize) -> &Priority {
+        &self.priorities[urgency.min(self.max_urgency())]
+    }
+}
 
 impl Default for Theme {
     fn default() -> Self {
@@ -26,6 +109,12 @@ impl Default for Theme {
             active_highlight: Color::from_str("#b7bdf8").unwrap(),
             inactive_highlight: Color::from_str("#6e738d").unwrap(),
             item_highlight: Color::from_str("#6e738d").unwrap(),
+
+            overdue: Color::Red,
+
+            priorities: Self::default_priorities(),
+
+            monochrome: false,
         }
     }
-}
\ No newline at end of file
+}
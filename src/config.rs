@@ -1,10 +1,17 @@
-use crate::colors::Theme;
+use crate::colors::{Priority, Theme};
+use crate::keymap::{Action, KeyMap, Keys};
+use crate::sqlite::SqliteBackend;
+use crate::store::{JsonBackend, Store, StoreBackend};
 use directories::ProjectDirs;
-use std::fs;
-use std::path::PathBuf;
+use ratatui::style::Color;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 #[derive(Serialize, Deserialize)]
 pub struct ThemeConfig {
@@ -16,50 +23,330 @@ pub struct ThemeConfig {
     pub active_highlight: String,
     pub inactive_highlight: String,
     pub highlight_text_secondary: String,
+
+    #[serde(default)]
+    pub overdue: Option<String>,
+
+    #[serde(default)]
+    pub priorities: Vec<PriorityConfig>,
 }
 
-impl Into<Theme> for ThemeConfig {
-    fn into(self) -> Theme {
-        Theme {
-            text: self.text.parse().unwrap(),
-            text_dark: self.text_dark.parse().unwrap(),
-            text_completed: self.text_completed.parse().unwrap(),
-            item_highlight: self.item_highlight.parse().unwrap(),
+/// One priority level. Either field can be left out to keep the default
+/// glyph or color of that level, which only the first four levels have.
+#[derive(Serialize, Deserialize)]
+pub struct PriorityConfig {
+    #[serde(default)]
+    pub glyph: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
 
-            active_highlight: self.active_highlight.parse().unwrap(),
-            inactive_highlight: self.inactive_highlight.parse().unwrap(),
-            highlight_text_secondary: self.highlight_text_secondary.parse().unwrap(),
-        }
+/// The number keys set priorities directly, so there can't be more levels.
+const MAX_PRIORITIES: usize = 9;
+
+impl TryFrom<ThemeConfig> for Theme {
+    type Error = String;
+
+    fn try_from(config: ThemeConfig) -> Result<Self, Self::Error> {
+        Ok(Theme {
+            text: parse_color("text", &config.text)?,
+            text_dark: parse_color("text_dark", &config.text_dark)?,
+            text_completed: parse_color("text_completed", &config.text_completed)?,
+            item_highlight: parse_color("item_highlight", &config.item_highlight)?,
+
+            active_highlight: parse_color("active_highlight", &config.active_highlight)?,
+            inactive_highlight: parse_color("inactive_highlight", &config.inactive_highlight)?,
+            highlight_text_secondary: parse_color(
+                "highlight_text_secondary",
+                &config.highlight_text_secondary,
+            )?,
+
+            overdue: match &config.overdue {
+                Some(overdue) => parse_color("overdue", overdue)?,
+                None => Color::Red,
+            },
+
+            monochrome: false,
+
+            priorities: parse_priorities(config.priorities)?,
+        })
     }
 }
 
+/// The configured priority levels, or the defaults when none are given.
+fn parse_priorities(config: Vec<PriorityConfig>) -> Result<Vec<Priority>, String> {
+    if config.is_empty() {
+        return Ok(Theme::default_priorities());
+    }
+    if config.len() > MAX_PRIORITIES {
+        return Err(format!(
+            "{} priorities given, at most {} are supported",
+            config.len(),
+            MAX_PRIORITIES
+        ));
+    }
+    let mut defaults = Theme::default_priorities().into_iter();
+    config
+        .into_iter()
+        .enumerate()
+        .map(|(index, p)| {
+            let default = defaults.next();
+            let color = match (&p.color, &default) {
+                (Some(color), _) => parse_color("priorities", color)?,
+                (None, Some(default)) => default.color,
+                (None, None) => return Err(format!("priority {} needs a color", index + 1)),
+            };
+            let glyph = match (p.glyph, default) {
+                (Some(glyph), _) => glyph,
+                (None, Some(default)) => default.glyph,
+                (None, None) => return Err(format!("priority {} needs a glyph", index + 1)),
+            };
+            Ok(Priority { glyph, color })
+        })
+        .collect()
+}
+
+fn parse_color(field: &str, value: &str) -> Result<Color, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid color {:?} for {}", value, field))
+}
+
+/// General settings read from `config.yaml`. Every field is optional.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Deepest nesting, counted in levels, that pasting as a child may create.
+    pub max_depth: Option<usize>,
+    /// Milliseconds between ticks, the periodic work done while no key is
+    /// pressed.
+    pub tick_rate_ms: Option<u64>,
+    /// Ask before `x` cuts a multi-selection. On unless set to false.
+    pub confirm_multi_cut: Option<bool>,
+    /// Ask before `x` cuts a single workspace, or a todo with children. On
+    /// unless set to false.
+    pub confirm_cut: Option<bool>,
+    /// Spaces per nesting level in both panes, from 1 to 4. Defaults to 2.
+    pub indent_width: Option<usize>,
+    /// Draw `├─`/`└─` guide lines in front of nested items instead of plain
+    /// indentation.
+    pub tree_guides: Option<bool>,
+    /// Whether `c` on a workspace also covers nested todos and child
+    /// workspaces, rather than only its top level todos. Defaults to true.
+    pub complete_workspace_recursively: Option<bool>,
+    /// Collapse a parent todo once it and all of its children are done. It
+    /// can still be expanded with `l`.
+    pub auto_collapse_done: Option<bool>,
+    /// Mark a parent todo done once `c` completes the last of its children,
+    /// and reopen it when one of them is reopened.
+    pub auto_complete_parents: Option<bool>,
+    /// Show the ` done/total` badge after parent todos. Defaults to true and
+    /// can be flipped at runtime with `#`.
+    pub show_child_counts: Option<bool>,
+    /// Keys that quit outside of editing, written as `q`, `esc` or `ctrl-c`.
+    /// Defaults to all three. `:q` always works.
+    pub quit_keys: Option<Vec<String>>,
+    /// Whether `+` on the highest priority goes back to the lowest, and `_`
+    /// on the lowest to the highest.
+    pub wrap_priority: Option<bool>,
+    /// Start with the panes drawn without borders and padding, see
+    /// `:compact`.
+    pub compact: Option<bool>,
+    /// Collapse expanded workspaces that haven't been selected, or had a
+    /// selected workspace inside them, for this many seconds. Off when unset.
+    pub collapse_idle_workspaces_secs: Option<u64>,
+    /// What `c` does with a todo besides marking it done. Defaults to
+    /// leaving it in place.
+    pub complete_action: Option<CompleteAction>,
+    /// Seconds between saves of the data file while running, 0 to only save
+    /// on quit and with `:w`. Defaults to 60.
+    pub autosave_secs: Option<u64>,
+    /// How many earlier versions of the data file to keep as `doneit.json.1`,
+    /// the newest, up to `doneit.json.<backups>`. Defaults to 5.
+    pub backups: Option<usize>,
+    /// How the todos are stored. Defaults to a JSON file.
+    pub storage: Option<Storage>,
+    /// Select items by clicking them. Defaults to true, turn it off to keep
+    /// the terminal's own text selection.
+    pub mouse: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// `doneit.json`, rewritten in full on every save.
+    #[default]
+    Json,
+    /// `doneit.db`, a SQLite database with a row per workspace and todo.
+    Sqlite,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompleteAction {
+    /// Only change the status.
+    #[default]
+    Toggle,
+    /// Move it below its siblings.
+    MoveToBottom,
+    /// Move it to the root `Archive` workspace, created when missing.
+    Archive,
+}
+
 fn get_project_dirs() -> ProjectDirs {
-    ProjectDirs::from("", "", "doneit".into())
-        .expect("Failed to get project directories".into())
+    ProjectDirs::from("", "", "doneit").expect("Failed to get project directories")
 }
 
-pub fn get_data_file_path() -> Result<PathBuf, std::io::Error> {
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Picks the profile for the rest of the run, `name` or else `DONEIT_PROFILE`.
+/// Without one the plain `doneit.json` and `theme.yaml` are used.
+pub fn init_profile(name: Option<String>) {
+    let name = name.or_else(|| {
+        std::env::var("DONEIT_PROFILE")
+            .ok()
+            .filter(|name| !name.is_empty())
+    });
+    PROFILE.get_or_init(|| name);
+}
+
+fn profile() -> Option<&'static str> {
+    PROFILE.get().and_then(|name| name.as_deref())
+}
+
+static DATA_FILE: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Uses `path` instead of the data file in the data directory for the rest
+/// of the run.
+pub fn init_data_file(path: Option<PathBuf>) {
+    DATA_FILE.get_or_init(|| path);
+}
+
+/// The data directory, created when missing.
+fn get_data_dir() -> Result<PathBuf, std::io::Error> {
     let proj_dirs = get_project_dirs();
     let data_dir = proj_dirs.data_dir();
     if !data_dir.exists() {
         fs::create_dir_all(data_dir)?;
     }
+    Ok(data_dir.to_path_buf())
+}
 
-    Ok(data_dir.join("doneit.json"))
+/// `--file` when given, else `doneit.json` or the profile's file in the data
+/// directory.
+pub fn get_data_file_path() -> Result<PathBuf, std::io::Error> {
+    if let Some(path) = DATA_FILE.get().and_then(Option::as_ref) {
+        return Ok(path.clone());
+    }
+    let data_dir = get_data_dir()?;
+    Ok(match profile() {
+        Some(name) => data_dir.join(format!("doneit.{}.json", name)),
+        None => data_dir.join("doneit.json"),
+    })
 }
 
-pub fn get_theme() -> Theme {
-    let proj_dirs = get_project_dirs();
-    let config_dir = proj_dirs.config_dir();
-    let theme_file_path = config_dir.join("theme.yaml");
-
-    if theme_file_path.exists() {
-        let mut file = File::open(theme_file_path).expect("Failed to open theme file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).expect("Failed to read theme file");
-        let theme_config: ThemeConfig = serde_yaml::from_str(&contents).expect("Failed to parse theme file");
-        theme_config.into()
-    } else {
-        Theme::default()
+/// The backend picked by `storage`, kept under the data file's name. A
+/// `--file` path is used as given whatever the storage.
+pub fn get_backend(config: &Config) -> Result<Box<dyn StoreBackend>, std::io::Error> {
+    let path = get_data_file_path()?;
+    Ok(match config.storage.unwrap_or_default() {
+        Storage::Json => Box::new(JsonBackend {
+            path,
+            backups: config.backups.unwrap_or(DEFAULT_BACKUPS),
+        }),
+        Storage::Sqlite if DATA_FILE.get().is_some_and(Option::is_some) => {
+            Box::new(SqliteBackend::new(path))
+        }
+        Storage::Sqlite => Box::new(SqliteBackend::new(path.with_extension("db"))),
+    })
+}
+
+/// Used when `backups` isn't configured.
+pub const DEFAULT_BACKUPS: usize = 5;
+
+/// Writes `store` to the data file at `path` after shifting the current file
+/// into the numbered backups. Nothing is written when the contents didn't
+/// change, so frequent autosaves don't push the useful backups out.
+pub fn save_data_file(store: &Store, path: &Path, backups: usize) -> io::Result<()> {
+    let json = serde_json::to_vec(store)?;
+    if fs::read(path).is_ok_and(|current| current == json) {
+        return Ok(());
+    }
+    if backups > 0 && path.exists() {
+        for n in (1..backups).rev() {
+            let backup = backup_path(path, n);
+            if backup.exists() {
+                fs::rename(&backup, backup_path(path, n + 1))?;
+            }
+        }
+        fs::copy(path, backup_path(path, 1))?;
+    }
+    fs::write(path, json)
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+pub fn get_templates_file_path() -> Result<PathBuf, std::io::Error> {
+    Ok(get_data_dir()?.join("templates.json"))
+}
+
+pub fn get_activity_log_path() -> Result<PathBuf, std::io::Error> {
+    Ok(get_data_dir()?.join("activity.log"))
+}
+
+/// Reads `name` from the config directory, or `None` when it doesn't exist.
+fn read_config_file(name: &str) -> Result<Option<String>, String> {
+    let path = get_project_dirs().config_dir().join(name);
+    if !path.exists() {
+        return Ok(None);
     }
+    let mut contents = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("Couldn't read {}: {}", name, e))?;
+    Ok(Some(contents))
+}
+
+/// The theme from `theme.<profile>.yaml`, then `theme.yaml`, or the default
+/// one when neither exists. Errors are meant to be shown while falling back
+/// to the default.
+pub fn get_theme() -> Result<Theme, String> {
+    let profile_file = profile().map(|name| format!("theme.{}.yaml", name));
+    for name in profile_file
+        .iter()
+        .map(String::as_str)
+        .chain(["theme.yaml"])
+    {
+        if let Some(contents) = read_config_file(name)? {
+            let theme_config: ThemeConfig = serde_yaml::from_str(&contents)
+                .map_err(|e| format!("Couldn't parse {}: {}", name, e))?;
+            return Theme::try_from(theme_config)
+                .map_err(|e| format!("Couldn't load {}: {}", name, e));
+        }
+    }
+    Ok(Theme::default())
+}
+
+/// The settings from `config.yaml`, or the defaults when there is no such
+/// file. Errors are meant to be shown while falling back to the defaults.
+pub fn get_config() -> Result<Config, String> {
+    let Some(contents) = read_config_file("config.yaml")? else {
+        return Ok(Config::default());
+    };
+    serde_yaml::from_str(&contents).map_err(|e| format!("Couldn't parse config.yaml: {}", e))
+}
+
+/// The rebindings from `keys.yaml`, mapping action names to a key or a list
+/// of keys. Errors are meant to be shown while keeping the default keys.
+pub fn get_keymap() -> Result<KeyMap, String> {
+    let Some(contents) = read_config_file("keys.yaml")? else {
+        return Ok(KeyMap::default());
+    };
+    let bindings: HashMap<Action, Keys> =
+        serde_yaml::from_str(&contents).map_err(|e| format!("Couldn't parse keys.yaml: {}", e))?;
+    KeyMap::new(bindings).map_err(|e| format!("Couldn't load keys.yaml: {}", e))
 }
@@ -0,0 +1,139 @@
+use chrono::{
+    DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday,
+};
+use std::time::SystemTime;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Parses a due date typed as `2024-12-31` or `2024-12-31 15:00`, in local
+/// time, or relative to now as `today`, `tomorrow`, `+3d`, `+2w` or a weekday
+/// such as `mon`. A date without a time is due at local midnight, which is
+/// also how [`has_time`] tells the two apart.
+pub fn parse_due(input: &str) -> Result<SystemTime, String> {
+    parse_due_at(input, SystemTime::now())
+}
+
+/// [`parse_due`] with relative dates counted from `now`.
+pub fn parse_due_at(input: &str, now: SystemTime) -> Result<SystemTime, String> {
+    let input = input.trim();
+    let naive = match parse_relative(input, DateTime::<Local>::from(now).date_naive()) {
+        Some(date) => date.and_time(NaiveTime::MIN),
+        None => NaiveDateTime::parse_from_str(input, DATE_TIME_FORMAT)
+            .or_else(|_| {
+                NaiveDate::parse_from_str(input, DATE_FORMAT)
+                    .map(|date| date.and_time(NaiveTime::MIN))
+            })
+            .map_err(|_| {
+                format!(
+                    "Invalid date: {}, expected YYYY-MM-DD [HH:MM], today, tomorrow, +Nd, +Nw or a weekday",
+                    input
+                )
+            })?,
+    };
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or_else(|| format!("{} doesn't exist in the local time zone", input))
+}
+
+/// The day `input` refers to counting from `today`. A weekday means the next
+/// one after today, so `mon` on a Monday is a week out.
+fn parse_relative(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let input = input.to_lowercase();
+    match input.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return Some(today + Days::new(1)),
+        _ => {}
+    }
+
+    if let Some(offset) = input.strip_prefix('+') {
+        let days = if let Some(count) = offset.strip_suffix('d') {
+            count.parse().ok()?
+        } else if let Some(count) = offset.strip_suffix('w') {
+            count.parse::<u64>().ok()?.checked_mul(7)?
+        } else {
+            return None;
+        };
+        return today.checked_add_days(Days::new(days));
+    }
+
+    let weekday: Weekday = input.parse().ok()?;
+    let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    Some(today + Days::new(if ahead == 0 { 7 } else { ahead as u64 }))
+}
+
+/// Whether `due` carries a time of day rather than just a date.
+pub fn has_time(due: SystemTime) -> bool {
+    DateTime::<Local>::from(due).time() != NaiveTime::MIN
+}
+
+/// Formats `due` in local time, in the same form [`parse_due`] reads.
+pub fn format_due(due: SystemTime) -> String {
+    let format = if has_time(due) {
+        DATE_TIME_FORMAT
+    } else {
+        DATE_FORMAT
+    };
+    DateTime::<Local>::from(due).format(format).to_string()
+}
+
+/// A todo with a time is overdue once that instant has passed, one with just a
+/// date only from the next day on.
+pub fn is_overdue(due: SystemTime, now: SystemTime) -> bool {
+    if has_time(due) {
+        due < now
+    } else {
+        DateTime::<Local>::from(due).date_naive() < DateTime::<Local>::from(now).date_naive()
+    }
+}
+
+/// Whole local days from `now` until `due`, negative once that day is past.
+pub fn days_until(due: SystemTime, now: SystemTime) -> i64 {
+    let due = DateTime::<Local>::from(due).date_naive();
+    let today = DateTime::<Local>::from(now).date_naive();
+    (due - today).num_days()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Noon on Wednesday 2024-05-15, local time.
+    fn now() -> SystemTime {
+        Local
+            .with_ymd_and_hms(2024, 5, 15, 12, 0, 0)
+            .unwrap()
+            .into()
+    }
+
+    fn due(input: &str) -> Result<String, String> {
+        parse_due_at(input, now()).map(format_due)
+    }
+
+    #[test]
+    fn relative_dates_count_from_now() {
+        assert_eq!(due("today").unwrap(), "2024-05-15");
+        assert_eq!(due("Tomorrow").unwrap(), "2024-05-16");
+        assert_eq!(due("+3d").unwrap(), "2024-05-18");
+        assert_eq!(due("+2w").unwrap(), "2024-05-29");
+        assert_eq!(due("fri").unwrap(), "2024-05-17");
+        assert_eq!(due("mon").unwrap(), "2024-05-20");
+        assert_eq!(due("wed").unwrap(), "2024-05-22");
+    }
+
+    #[test]
+    fn absolute_dates_keep_their_time() {
+        assert_eq!(due("2024-12-31").unwrap(), "2024-12-31");
+        assert_eq!(due(" 2024-12-31 15:00 ").unwrap(), "2024-12-31 15:00");
+    }
+
+    #[test]
+    fn malformed_offsets_are_rejected() {
+        for input in ["+", "+d", "+3", "+3x", "+é", "+3é", "+-1d", "2024-13-01"] {
+            assert!(due(input).is_err(), "{} parsed", input);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::store::{self, Status, Store, Todo, Workspace};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::time::SystemTime;
+
+/// Workspace for tasks without a project.
+const NO_PROJECT_WORKSPACE: &str = "Inbox";
+
+/// One entry of `task export`. Only the fields doneit has a place for are
+/// read.
+#[derive(Deserialize)]
+struct Task {
+    description: String,
+    status: String,
+    priority: Option<String>,
+    due: Option<String>,
+    project: Option<String>,
+}
+
+/// Reads the JSON array printed by Taskwarrior's `task export`. Projects
+/// become workspaces, `Home.Garden` being `Garden` inside `Home`, and H, M
+/// and L priorities are spread over the levels above the lowest, up to
+/// `max_urgency`. Deleted tasks are skipped.
+pub fn from_taskwarrior(json: &str, max_urgency: usize) -> Result<Store, String> {
+    let tasks: Vec<Task> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+    let mut store = Store::default();
+    for task in tasks {
+        let status = match task.status.as_str() {
+            "deleted" => continue,
+            "completed" => Status::Done,
+            _ => Status::Todo,
+        };
+        let rank = match task.priority.as_deref() {
+            Some("H") => 3,
+            Some("M") => 2,
+            Some("L") => 1,
+            _ => 0,
+        };
+        // Rounded up so L stays above the lowest level whenever there is one
+        let urgency = (rank * max_urgency).div_ceil(3);
+        let due = match &task.due {
+            Some(due) => Some(parse_date(due)?),
+            None => None,
+        };
+        let todo = Todo {
+            status,
+            urgency,
+            due,
+            ..Todo::new(&task.description)
+        };
+
+        let project = task.project.as_deref().unwrap_or(NO_PROJECT_WORKSPACE);
+        project_workspace(&mut store.workspaces, project)
+            .todos
+            .push(todo);
+    }
+    Ok(store)
+}
+
+/// Taskwarrior writes dates in UTC as `20241231T230000Z`.
+fn parse_date(date: &str) -> Result<SystemTime, String> {
+    NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ")
+        .map(|naive| naive.and_utc().into())
+        .map_err(|_| format!("Invalid Taskwarrior date: {}", date))
+}
+
+/// The workspace for a dotted project path, created along the way where
+/// missing.
+fn project_workspace<'a>(workspaces: &'a mut Vec<Workspace>, project: &str) -> &'a mut Workspace {
+    let (name, rest) = match project.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (project, None),
+    };
+    let index = match workspaces.iter().position(|w| w.description == name) {
+        Some(index) => index,
+        None => {
+            workspaces.push(Workspace {
+                id: store::new_id(),
+                description: name.to_string(),
+                color: None,
+                children: Vec::new(),
+                todos: Vec::new(),
+                opened_todos: Vec::new(),
+            });
+            workspaces.len() - 1
+        }
+    };
+    match rest {
+        Some(rest) => project_workspace(&mut workspaces[index].children, rest),
+        None => &mut workspaces[index],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = include_str!("../tests/fixtures/taskwarrior.json");
+
+    #[test]
+    fn projects_become_nested_workspaces() {
+        let store = from_taskwarrior(EXPORT, 3).unwrap();
+
+        let names: Vec<&str> = store
+            .workspaces
+            .iter()
+            .map(|w| w.description.as_str())
+            .collect();
+        assert_eq!(names, ["Home", "Inbox"]);
+        let home = &store.workspaces[0];
+        assert_eq!(home.todos[0].description, "Pay rent");
+        assert!(home.todos[0].status.is_done());
+        let garden = &home.children[0];
+        assert_eq!(garden.description, "Garden");
+        assert_eq!(garden.todos.len(), 2);
+        assert_eq!(
+            garden.todos[0].due,
+            Some(parse_date("20241231T230000Z").unwrap())
+        );
+        assert_eq!(store.workspaces[1].todos[0].description, "Call the bank");
+    }
+
+    #[test]
+    fn priorities_scale_to_the_theme() {
+        let urgencies = |max_urgency| {
+            let store = from_taskwarrior(EXPORT, max_urgency).unwrap();
+            let home = &store.workspaces[0];
+            [
+                home.children[0].todos[0].urgency,
+                home.todos[0].urgency,
+                home.children[0].todos[1].urgency,
+                store.workspaces[1].todos[0].urgency,
+            ]
+        };
+        assert_eq!(urgencies(3), [3, 2, 1, 0]);
+        assert_eq!(urgencies(8), [8, 6, 3, 0]);
+        assert_eq!(urgencies(1), [1, 1, 1, 0]);
+        assert_eq!(urgencies(0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn malformed_dates_are_reported() {
+        let json = r#"[{"description":"a","status":"pending","due":"2024-12-31"}]"#;
+        assert_eq!(
+            from_taskwarrior(json, 3).err().unwrap(),
+            "Invalid Taskwarrior date: 2024-12-31"
+        );
+    }
+}
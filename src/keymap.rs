@@ -0,0 +1,275 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Something a key does, named as in `keys.yaml`. Only single keys outside
+/// of typing can be rebound, so sequences like `gJ` and `zz` and the keys of
+/// the edit, search and command lines keep their defaults. Quitting has its
+/// own `quit_keys` setting.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    SwitchPane,
+    MoveDown,
+    MoveUp,
+    MoveItemDown,
+    MoveItemUp,
+    Collapse,
+    Expand,
+    Indent,
+    Outdent,
+    Focus,
+    Unfocus,
+    Edit,
+    SetDue,
+    EditTags,
+    Snooze,
+    ToggleSnoozed,
+    ToggleHideCompleted,
+    AddSibling,
+    AddChild,
+    ToggleComplete,
+    UndoComplete,
+    Pin,
+    MoveUnder,
+    MarkForMove,
+    ToggleChildCounts,
+    Yank,
+    CopyView,
+    Cut,
+    Paste,
+    PasteChild,
+    IncreaseUrgency,
+    DecreaseUrgency,
+    IncreaseEffort,
+    DecreaseEffort,
+    CycleColor,
+    Duplicate,
+    ImportMarkdown,
+    Sort,
+    Search,
+    Filter,
+    NextMatch,
+    PreviousMatch,
+    Select,
+    SelectAll,
+    Command,
+    Help,
+    ExportMarkdown,
+}
+
+impl Action {
+    /// What the action is bound to by default, which is also the key the
+    /// handlers match on.
+    fn default_key(self) -> (KeyModifiers, KeyCode) {
+        let key = match self {
+            Action::SwitchPane => return (KeyModifiers::NONE, KeyCode::Tab),
+            Action::MoveDown => 'j',
+            Action::MoveUp => 'k',
+            Action::MoveItemDown => 'J',
+            Action::MoveItemUp => 'K',
+            Action::Collapse => 'h',
+            Action::Expand => 'l',
+            Action::Indent => return (KeyModifiers::ALT, KeyCode::Char('l')),
+            Action::Outdent => return (KeyModifiers::ALT, KeyCode::Char('h')),
+            Action::Focus => 'f',
+            Action::Unfocus => 'F',
+            Action::Edit => 'i',
+            Action::SetDue => 'd',
+            Action::EditTags => 't',
+            Action::Snooze => 'S',
+            Action::ToggleSnoozed => 'Z',
+            Action::ToggleHideCompleted => 'C',
+            Action::AddSibling => 'a',
+            Action::AddChild => 'A',
+            Action::ToggleComplete => 'c',
+            Action::UndoComplete => 'u',
+            Action::Pin => return (KeyModifiers::CONTROL, KeyCode::Char('p')),
+            Action::MoveUnder => 'm',
+            Action::MarkForMove => 'M',
+            Action::ToggleChildCounts => '#',
+            Action::Yank => 'y',
+            Action::CopyView => 'Y',
+            Action::Cut => 'x',
+            Action::Paste => 'p',
+            Action::PasteChild => 'P',
+            Action::IncreaseUrgency => '+',
+            Action::DecreaseUrgency => '_',
+            Action::IncreaseEffort => '>',
+            Action::DecreaseEffort => '<',
+            Action::CycleColor => 'C',
+            Action::Duplicate => 'D',
+            Action::ImportMarkdown => 'I',
+            Action::Sort => return (KeyModifiers::CONTROL, KeyCode::Char('s')),
+            Action::Search => '/',
+            Action::Filter => return (KeyModifiers::CONTROL, KeyCode::Char('f')),
+            Action::NextMatch => 'n',
+            Action::PreviousMatch => 'N',
+            Action::Select => ' ',
+            Action::SelectAll => return (KeyModifiers::CONTROL, KeyCode::Char('a')),
+            Action::Command => ':',
+            Action::Help => '?',
+            Action::ExportMarkdown => return (KeyModifiers::CONTROL, KeyCode::Char('e')),
+        };
+        (KeyModifiers::NONE, KeyCode::Char(key))
+    }
+
+    /// The one pane the action works in, for the ones whose default key does
+    /// something else in the other pane.
+    fn pane(self) -> Option<Pane> {
+        match self {
+            Action::ToggleHideCompleted => Some(Pane::Todos),
+            Action::CycleColor => Some(Pane::Workspaces),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pane {
+    Workspaces,
+    Todos,
+}
+
+/// A key or a list of keys in `keys.yaml`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// The keys from `keys.yaml`. Actions that aren't listed keep their default
+/// key, and a listed action no longer answers to its default one.
+#[derive(Default)]
+pub struct KeyMap {
+    bindings: HashMap<(KeyModifiers, KeyCode), Action>,
+    /// Default keys of the rebound actions, with the pane they are limited to.
+    unbound: HashSet<((KeyModifiers, KeyCode), Option<Pane>)>,
+}
+
+impl KeyMap {
+    pub fn new(config: HashMap<Action, Keys>) -> Result<Self, String> {
+        let mut keymap = KeyMap::default();
+        for (action, keys) in config {
+            keymap
+                .unbound
+                .insert((normalize(action.default_key()), action.pane()));
+            let specs = match keys {
+                Keys::One(spec) => vec![spec],
+                Keys::Many(specs) => specs,
+            };
+            for spec in specs {
+                let key = parse_key(&spec)?;
+                if keymap.bindings.insert(key, action).is_some() {
+                    return Err(format!("{} is bound to more than one action", spec));
+                }
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// The key the handlers of `pane` should see for `key`: the default key
+    /// of the action it's bound to, or `key` itself when it isn't bound.
+    /// `None` for the default key of a rebound action.
+    pub fn resolve(&self, key: KeyEvent, pane: Pane) -> Option<KeyEvent> {
+        let in_pane = |only: Option<Pane>| only.is_none_or(|only| only == pane);
+        let pressed = normalize((key.modifiers, key.code));
+        if let Some(action) = self.bindings.get(&pressed).filter(|a| in_pane(a.pane())) {
+            let (modifiers, code) = action.default_key();
+            return Some(KeyEvent::new(code, modifiers));
+        }
+        if self
+            .unbound
+            .iter()
+            .any(|(default, only)| *default == pressed && in_pane(*only))
+        {
+            return None;
+        }
+        Some(key)
+    }
+}
+
+/// Reads a key written as `j`, `ctrl-p`, `alt-l`, `space`, `tab`, `esc`,
+/// `enter`, `backspace` or an arrow such as `up`.
+pub fn parse_key(spec: &str) -> Result<(KeyModifiers, KeyCode), String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = spec;
+    loop {
+        if let Some(rest) = name.strip_prefix("ctrl-").filter(|rest| !rest.is_empty()) {
+            modifiers |= KeyModifiers::CONTROL;
+            name = rest;
+        } else if let Some(rest) = name.strip_prefix("alt-").filter(|rest| !rest.is_empty()) {
+            modifiers |= KeyModifiers::ALT;
+            name = rest;
+        } else {
+            break;
+        }
+    }
+    let code = match name {
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "enter" => KeyCode::Enter,
+        "backspace" => KeyCode::Backspace,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("unknown key {:?}", spec)),
+            }
+        }
+    };
+    Ok(normalize((modifiers, code)))
+}
+
+/// Whether `key` is the key written as `spec`.
+pub fn key_matches(spec: &str, key: KeyEvent) -> bool {
+    parse_key(spec).is_ok_and(|spec| spec == normalize((key.modifiers, key.code)))
+}
+
+/// Terminals report shifted letters as uppercase, with or without `SHIFT`,
+/// and control letters in either case, so both are evened out.
+fn normalize((modifiers, code): (KeyModifiers, KeyCode)) -> (KeyModifiers, KeyCode) {
+    match code {
+        KeyCode::Char(c) => {
+            let c = if modifiers.contains(KeyModifiers::CONTROL) {
+                c.to_ascii_lowercase()
+            } else {
+                c
+            };
+            (modifiers - KeyModifiers::SHIFT, KeyCode::Char(c))
+        }
+        _ => (modifiers, code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn keys_shared_by_the_panes_are_rebound_separately() {
+        let keymap = KeyMap::new(HashMap::from([(
+            Action::CycleColor,
+            Keys::One("o".to_string()),
+        )]))
+        .unwrap();
+
+        assert_eq!(
+            keymap.resolve(press('o'), Pane::Workspaces),
+            Some(press('C'))
+        );
+        assert_eq!(keymap.resolve(press('C'), Pane::Workspaces), None);
+        assert_eq!(keymap.resolve(press('C'), Pane::Todos), Some(press('C')));
+        assert_eq!(keymap.resolve(press('o'), Pane::Todos), Some(press('o')));
+    }
+}
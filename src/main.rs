@@ -1,22 +1,133 @@
 pub use app::App;
-use store::Store;
+use cli::Command;
+use std::io::Read;
+use store::{Store, StoreBackend};
 
+mod activity;
 pub mod app;
-mod store;
+mod cli;
 mod colors;
 mod config;
-
-
+mod dates;
+mod import;
+mod keymap;
+mod query;
+mod sqlite;
+mod store;
 
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
-    let terminal = ratatui::init();
-    let data_path =  config::get_data_file_path()?;
-    let store = Store::from_json_file(&data_path).unwrap_or_default();
-    let theme = config::get_theme();
-    let mut app = App::new(store, theme);
+    // The theme, and with it the highest priority, depends on the profile
+    let max_urgency = |profile: Option<&str>| {
+        config::init_profile(profile.map(String::from));
+        config::get_theme().unwrap_or_default().max_urgency()
+    };
+    let args = match cli::parse(std::env::args().skip(1), max_urgency) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    config::init_profile(args.profile);
+    config::init_data_file(args.file);
+    // Shown in the TUI, the subcommands just use the defaults
+    let config = config::get_config();
+    let backend = config::get_backend(config.as_ref().unwrap_or(&config::Config::default()))?;
+
+    match args.command {
+        Command::Interactive => run_interactive(backend.as_ref(), config, args.no_color, args.safe),
+        Command::Add(new) => {
+            let mut input = String::new();
+            if new.description.is_none() {
+                std::io::stdin().read_to_string(&mut input)?;
+            }
+            let mut store = load_store(backend.as_ref())?;
+            store
+                .workspace_by_name_mut(&new.workspace)
+                .todos
+                .extend(new.todos(&input));
+            if !args.safe {
+                backend.save(&store)?;
+            }
+            Ok(())
+        }
+        Command::List(filter) => {
+            let store = load_store(backend.as_ref())?;
+            match cli::list(&store, &filter) {
+                Ok(text) => print!("{}", text),
+                Err(message) => {
+                    eprintln!("{}", message);
+                    std::process::exit(1);
+                }
+            }
+            Ok(())
+        }
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+    }
+}
+
+/// Loads the store for the subcommands. Unlike the TUI this fails instead of
+/// falling back to an empty store, saving that would wipe the existing todos.
+fn load_store(backend: &dyn StoreBackend) -> std::io::Result<Store> {
+    if backend.path().exists() {
+        backend.load()
+    } else {
+        Ok(Store::default())
+    }
+}
+
+fn run_interactive(
+    backend: &dyn StoreBackend,
+    config: Result<config::Config, String>,
+    no_color: bool,
+    safe: bool,
+) -> color_eyre::Result<()> {
+    // Starting with an empty store would overwrite the todos on quit
+    let store = if backend.path().exists() {
+        match backend.load() {
+            Ok(store) => store,
+            Err(err) => {
+                eprintln!(
+                    "Could not read {}: {}\nFix it, or move it away to start over. Nothing was changed.",
+                    backend.path().display(),
+                    err
+                );
+                std::process::exit(1);
+            }
+        }
+    } else {
+        Store::example()
+    };
+    let mut warnings = Vec::new();
+    let mut theme = config::get_theme().unwrap_or_else(|e| {
+        warnings.push(e);
+        colors::Theme::default()
+    });
+    theme.monochrome =
+        no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let config = config.unwrap_or_else(|e| {
+        warnings.push(e);
+        config::Config::default()
+    });
+    let keymap = config::get_keymap().unwrap_or_else(|e| {
+        warnings.push(e);
+        keymap::KeyMap::default()
+    });
+    let terminal = app::init_terminal(config.mouse.unwrap_or(true));
+    let mut app = App::new(store, theme, config)
+        .with_keymap(keymap)
+        .with_safe_mode(safe);
+    for warning in warnings {
+        app.warn(warning);
+    }
     let result = app.run(terminal);
-    ratatui::restore();
-    app.get_store().to_json_file(&data_path)?;
+    app::restore_terminal();
+    if !safe {
+        backend.save(&app.get_store())?;
+    }
     result
 }
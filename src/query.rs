@@ -0,0 +1,123 @@
+use crate::dates;
+use crate::store::TodoItem;
+use std::time::SystemTime;
+
+/// A `/` search. Words such as `due:today`, `pri:high` or `#work` filter on that field
+/// of the todo, every other word has to appear in the description. All of
+/// them have to hold for a todo to match.
+pub struct Query {
+    words: Vec<String>,
+    /// Smart case: set once any word has an uppercase letter.
+    case_sensitive: bool,
+    predicates: Vec<Predicate>,
+}
+
+enum Predicate {
+    /// `due:none`
+    NoDue,
+    /// `due:overdue`, past due and not done yet.
+    Overdue,
+    /// `due:today` is `0..=0`, `due:+3` the next three days, `due:-3` the
+    /// last three.
+    DueWithin(i64, i64),
+    /// `pri:low`, `pri:high` or `pri:<level>`, 0 being the lowest.
+    Priority(usize),
+    /// `done:true` or `done:false`
+    Done(bool),
+    /// `tag:work` or `#work`, lowercased.
+    Tag(String),
+}
+
+impl Query {
+    /// Words that look like a predicate but don't parse as one, e.g. `due:soon`
+    /// or a link, are matched as plain text.
+    pub fn parse(search_str: &str, max_urgency: usize) -> Self {
+        let mut query = Query {
+            words: Vec::new(),
+            case_sensitive: false,
+            predicates: Vec::new(),
+        };
+        for word in search_str.split_whitespace() {
+            match Predicate::parse(word, max_urgency) {
+                Some(predicate) => query.predicates.push(predicate),
+                None => query.words.push(word.to_string()),
+            }
+        }
+        // Without predicates the text is matched as typed, spaces included.
+        if query.predicates.is_empty() {
+            query.words = vec![search_str.to_string()];
+        }
+        query.case_sensitive = query
+            .words
+            .iter()
+            .any(|word| word.chars().any(char::is_uppercase));
+        if !query.case_sensitive {
+            query.words = query.words.iter().map(|word| word.to_lowercase()).collect();
+        }
+        query
+    }
+
+    pub fn matches(&self, todo: &TodoItem, now: SystemTime) -> bool {
+        self.matches_text(&todo.description) && self.predicates.iter().all(|p| p.matches(todo, now))
+    }
+
+    /// Whether a workspace called `name` matches. Predicates are about todos,
+    /// so queries with any never match a workspace.
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.predicates.is_empty() && self.matches_text(name)
+    }
+
+    fn matches_text(&self, text: &str) -> bool {
+        let text = if self.case_sensitive {
+            text.to_string()
+        } else {
+            text.to_lowercase()
+        };
+        self.words.iter().all(|word| text.contains(word.as_str()))
+    }
+}
+
+impl Predicate {
+    fn parse(word: &str, max_urgency: usize) -> Option<Self> {
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            return Some(Predicate::Tag(tag.to_lowercase()));
+        }
+        let (field, value) = word.split_once(':')?;
+        let value = value.to_lowercase();
+        match field.to_lowercase().as_str() {
+            "due" => match value.as_str() {
+                "none" => Some(Predicate::NoDue),
+                "overdue" => Some(Predicate::Overdue),
+                "today" => Some(Predicate::DueWithin(0, 0)),
+                _ => {
+                    let days: i64 = value.parse().ok()?;
+                    Some(Predicate::DueWithin(days.min(0), days.max(0)))
+                }
+            },
+            "pri" => match value.as_str() {
+                "low" => Some(Predicate::Priority(0)),
+                "high" => Some(Predicate::Priority(max_urgency)),
+                _ => value.parse().ok().map(Predicate::Priority),
+            },
+            "done" => value.parse().ok().map(Predicate::Done),
+            "tag" if !value.is_empty() => Some(Predicate::Tag(value)),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, todo: &TodoItem, now: SystemTime) -> bool {
+        match self {
+            Predicate::NoDue => todo.due.is_none(),
+            Predicate::Overdue => {
+                !todo.status.is_done() && todo.due.is_some_and(|due| dates::is_overdue(due, now))
+            }
+            Predicate::DueWithin(from, to) => todo.due.is_some_and(|due| {
+                let days = dates::days_until(due, now);
+                *from <= days && days <= *to
+            }),
+            Predicate::Priority(urgency) => todo.urgency == *urgency,
+            Predicate::Done(done) => todo.status.is_done() == *done,
+            Predicate::Tag(tag) => todo.tags.iter().any(|t| t.to_lowercase() == *tag),
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use crate::store::{Status, Store, StoreBackend, Todo, Workspace, STORE_VERSION};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OpenFlags, Transaction};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS workspaces (
+    id TEXT PRIMARY KEY,
+    parent_id TEXT REFERENCES workspaces(id),
+    position INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    color TEXT,
+    opened_todos TEXT NOT NULL DEFAULT '[]'
+);
+CREATE TABLE IF NOT EXISTS todos (
+    id TEXT PRIMARY KEY,
+    workspace_id TEXT NOT NULL REFERENCES workspaces(id),
+    parent_id TEXT REFERENCES todos(id),
+    position INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    due TEXT,
+    effort INTEGER NOT NULL,
+    urgency INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    pinned INTEGER NOT NULL,
+    snooze_until TEXT
+);
+";
+
+/// Changes to `SCHEMA` for databases created before them, the first one
+/// bringing `user_version` to 1.
+const MIGRATIONS: [&str; 1] = ["ALTER TABLE todos ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';"];
+
+/// Keeps the store in a SQLite database, one row per workspace and todo with
+/// the parent's id and the position among its siblings.
+pub struct SqliteBackend {
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    pub fn new(path: PathBuf) -> Self {
+        SqliteBackend { path }
+    }
+
+    /// Opens the database for reading only, so loading never creates or
+    /// migrates it. That happens on the next save.
+    fn open_read_only(&self) -> rusqlite::Result<Connection> {
+        Connection::open_with_flags(&self.path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+    }
+
+    /// Opens the database for saving, creating it and bringing its tables up
+    /// to date first.
+    fn open_for_save(&self) -> rusqlite::Result<Connection> {
+        let mut connection = Connection::open(&self.path)?;
+        connection.execute_batch(SCHEMA)?;
+        let version = schema_version(&connection)?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            // Together, so an interrupted migration isn't run twice
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+        Ok(connection)
+    }
+}
+
+impl StoreBackend for SqliteBackend {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> io::Result<Store> {
+        load(&self.open_read_only().map_err(io::Error::other)?).map_err(io::Error::other)
+    }
+
+    /// Replaces every row in a single transaction, so a failed save leaves the
+    /// previous one intact.
+    fn save(&self, store: &Store) -> io::Result<()> {
+        let mut connection = self.open_for_save().map_err(io::Error::other)?;
+        let transaction = connection.transaction().map_err(io::Error::other)?;
+        save(&transaction, store)
+            .and_then(|()| transaction.commit())
+            .map_err(io::Error::other)
+    }
+}
+
+/// The `user_version` of the database, refusing one written by a newer doneit
+/// whose tables it doesn't know.
+fn schema_version(connection: &Connection) -> rusqlite::Result<usize> {
+    connection.query_row("PRAGMA user_version", [], |row| {
+        let version: usize = row.get(0)?;
+        if version > MIGRATIONS.len() {
+            return Err(rusqlite::Error::FromSqlConversionFailure(
+                0,
+                Type::Integer,
+                format!(
+                    "written by a newer doneit (schema version {}, this one reads up to {})",
+                    version,
+                    MIGRATIONS.len()
+                )
+                .into(),
+            ));
+        }
+        Ok(version)
+    })
+}
+
+fn load(connection: &Connection) -> rusqlite::Result<Store> {
+    // Databases not migrated yet, which only happens on save, lack the
+    // columns added since
+    let version = schema_version(connection)?;
+    let tags = if version >= 1 { "tags" } else { "'[]'" };
+
+    let mut todos: HashMap<String, Vec<Todo>> = HashMap::new();
+    let mut statement = connection.prepare(&format!(
+        "SELECT id, workspace_id, parent_id, description, due, effort, urgency, status, pinned, snooze_until, {}
+         FROM todos ORDER BY position",
+        tags
+    ))?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let workspace_id: String = row.get(1)?;
+        let parent_id: Option<String> = row.get(2)?;
+        let status: String = row.get(7)?;
+        let tags: String = row.get(10)?;
+        let todo = Todo {
+            id: row.get(0)?,
+            description: row.get(3)?,
+            due: row
+                .get::<_, Option<DateTime<Utc>>>(4)?
+                .map(SystemTime::from),
+            effort: row.get(5)?,
+            urgency: row.get(6)?,
+            status: match status.as_str() {
+                "todo" => Status::Todo,
+                "doing" => Status::Doing,
+                "done" => Status::Done,
+                _ => {
+                    return Err(rusqlite::Error::FromSqlConversionFailure(
+                        7,
+                        Type::Text,
+                        format!("unknown todo status '{}'", status).into(),
+                    ))
+                }
+            },
+            pinned: row.get(8)?,
+            snooze_until: row
+                .get::<_, Option<DateTime<Utc>>>(9)?
+                .map(SystemTime::from),
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+            pending: None,
+            children: Vec::new(),
+        };
+        // Top level todos are grouped by workspace, nested ones by parent
+        let group = parent_id.unwrap_or_else(|| workspace_group(&workspace_id));
+        todos.entry(group).or_default().push(todo);
+    }
+
+    let mut workspaces: HashMap<Option<String>, Vec<Workspace>> = HashMap::new();
+    let mut statement = connection.prepare(
+        "SELECT id, parent_id, description, color, opened_todos FROM workspaces ORDER BY position",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let opened_todos: String = row.get(4)?;
+        let workspace = Workspace {
+            id: row.get(0)?,
+            description: row.get(2)?,
+            color: row.get(3)?,
+            children: Vec::new(),
+            todos: Vec::new(),
+            opened_todos: serde_json::from_str(&opened_todos).unwrap_or_default(),
+        };
+        workspaces.entry(row.get(1)?).or_default().push(workspace);
+    }
+
+    fn attach_todos(todo: &mut Todo, todos: &mut HashMap<String, Vec<Todo>>) {
+        for mut child in todos.remove(&todo.id).unwrap_or_default() {
+            attach_todos(&mut child, todos);
+            todo.children.push(child);
+        }
+    }
+    fn attach_workspaces(
+        parent: Option<String>,
+        workspaces: &mut HashMap<Option<String>, Vec<Workspace>>,
+        todos: &mut HashMap<String, Vec<Todo>>,
+    ) -> Vec<Workspace> {
+        let mut level = workspaces.remove(&parent).unwrap_or_default();
+        for workspace in &mut level {
+            for mut todo in todos
+                .remove(&workspace_group(&workspace.id))
+                .unwrap_or_default()
+            {
+                attach_todos(&mut todo, todos);
+                workspace.todos.push(todo);
+            }
+            workspace.children = attach_workspaces(Some(workspace.id.clone()), workspaces, todos);
+        }
+        level
+    }
+
+    Ok(Store {
+        version: STORE_VERSION,
+        workspaces: attach_workspaces(None, &mut workspaces, &mut todos),
+    })
+}
+
+/// Key for the top level todos of a workspace among the todos grouped by
+/// parent id.
+fn workspace_group(workspace_id: &str) -> String {
+    format!("workspace:{}", workspace_id)
+}
+
+fn save(transaction: &Transaction, store: &Store) -> rusqlite::Result<()> {
+    transaction.execute("DELETE FROM todos", [])?;
+    transaction.execute("DELETE FROM workspaces", [])?;
+
+    fn save_todos(
+        transaction: &Transaction,
+        workspace_id: &str,
+        todos: &[Todo],
+    ) -> rusqlite::Result<()> {
+        let mut stack: Vec<(Option<&str>, usize, &Todo)> = todos
+            .iter()
+            .enumerate()
+            .map(|(position, todo)| (None, position, todo))
+            .collect();
+        while let Some((parent_id, position, todo)) = stack.pop() {
+            transaction.execute(
+                "INSERT INTO todos (id, workspace_id, parent_id, position, description, due, effort, urgency, status, pinned, snooze_until, tags)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    todo.id,
+                    workspace_id,
+                    parent_id,
+                    position,
+                    todo.description,
+                    todo.due.map(DateTime::<Utc>::from),
+                    todo.effort,
+                    todo.urgency,
+                    match todo.status {
+                        Status::Todo => "todo",
+                        Status::Doing => "doing",
+                        Status::Done => "done",
+                    },
+                    todo.pinned,
+                    todo.snooze_until.map(DateTime::<Utc>::from),
+                    serde_json::to_string(&todo.tags).unwrap_or_default(),
+                ],
+            )?;
+            stack.extend(
+                todo.children
+                    .iter()
+                    .enumerate()
+                    .map(|(position, child)| (Some(todo.id.as_str()), position, child)),
+            );
+        }
+        Ok(())
+    }
+    fn save_workspaces(
+        transaction: &Transaction,
+        parent_id: Option<&str>,
+        workspaces: &[Workspace],
+    ) -> rusqlite::Result<()> {
+        for (position, workspace) in workspaces.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO workspaces (id, parent_id, position, description, color, opened_todos)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    workspace.id,
+                    parent_id,
+                    position,
+                    workspace.description,
+                    workspace.color,
+                    serde_json::to_string(&workspace.opened_todos).unwrap_or_default(),
+                ],
+            )?;
+            save_todos(transaction, &workspace.id, &workspace.todos)?;
+            save_workspaces(transaction, Some(&workspace.id), &workspace.children)?;
+        }
+        Ok(())
+    }
+
+    save_workspaces(transaction, None, &store.workspaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_outline;
+    use std::fs;
+
+    fn backend(name: &str) -> SqliteBackend {
+        let path = std::env::temp_dir().join(format!("doneit-{}-{}.db", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        SqliteBackend::new(path)
+    }
+
+    #[test]
+    fn loading_a_missing_database_creates_nothing() {
+        let backend = backend("missing");
+        assert!(backend.load().is_err());
+        assert!(!backend.path().exists());
+    }
+
+    #[test]
+    fn old_databases_load_as_they_are_and_migrate_on_save() {
+        let backend = backend("old");
+        let connection = Connection::open(backend.path()).unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        connection
+            .execute(
+                "INSERT INTO workspaces (id, position, description) VALUES ('w', 0, 'Work')",
+                [],
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO todos (id, workspace_id, position, description, effort, urgency, status, pinned)
+                 VALUES ('a', 'w', 0, 'a', 0, 0, 'todo', 0)",
+                [],
+            )
+            .unwrap();
+        drop(connection);
+        let user_version = || {
+            Connection::open(backend.path())
+                .unwrap()
+                .query_row("PRAGMA user_version", [], |row| row.get::<_, usize>(0))
+                .unwrap()
+        };
+
+        let store = backend.load().unwrap();
+        assert_eq!(store.workspaces[0].todos[0].description, "a");
+        assert!(store.workspaces[0].todos[0].tags.is_empty());
+        assert_eq!(user_version(), 0);
+
+        backend.save(&store).unwrap();
+        assert_eq!(user_version(), MIGRATIONS.len());
+        fs::remove_file(backend.path()).unwrap();
+    }
+
+    #[test]
+    fn newer_databases_are_refused() {
+        let backend = backend("newer");
+        let connection = Connection::open(backend.path()).unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        connection
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        drop(connection);
+
+        assert!(backend.load().is_err());
+        assert!(backend.save(&Store::default()).is_err());
+        fs::remove_file(backend.path()).unwrap();
+    }
+
+    #[test]
+    fn unknown_statuses_fail_to_load() {
+        let backend = backend("status");
+        backend.save(&Store::default()).unwrap();
+        let connection = Connection::open(backend.path()).unwrap();
+        connection
+            .execute(
+                "INSERT INTO todos (id, workspace_id, position, description, effort, urgency, status, pinned)
+                 VALUES ('a', 'w', 0, 'a', 0, 0, 'waiting', 0)",
+                [],
+            )
+            .unwrap();
+        drop(connection);
+
+        assert!(backend.load().is_err());
+        fs::remove_file(backend.path()).unwrap();
+    }
+
+    #[test]
+    fn nested_todos_survive_a_round_trip() {
+        let backend = backend("round-trip");
+        let mut store = Store::default();
+        store.workspace_by_name_mut("Work").todos = parse_outline("a\n  [x] b\n    c\n  d\ne\n");
+        backend.save(&store).unwrap();
+
+        let loaded = backend.load().unwrap();
+        fs::remove_file(backend.path()).unwrap();
+        assert_eq!(
+            loaded.workspaces[0].to_markdown(),
+            store.workspaces[0].to_markdown()
+        );
+    }
+}
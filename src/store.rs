@@ -1,38 +1,405 @@
+use crate::{config, dates};
 use serde::{Deserialize, Serialize};
-use serde_json;
 use slotmap::{DefaultKey, SlotMap};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufReader, BufWriter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Produces the ids given to newly created workspaces and todos.
+pub type IdGenerator = Box<dyn FnMut() -> String>;
+
+thread_local! {
+    static ID_GENERATOR: RefCell<Option<IdGenerator>> = const { RefCell::new(None) };
+}
+
+/// Replaces the random UUIDs [`new_id`] hands out on this thread with
+/// predictable ones.
+#[cfg(test)]
+pub fn set_id_generator(id_generator: IdGenerator) {
+    ID_GENERATOR.with(|generator| *generator.borrow_mut() = Some(id_generator));
+}
+
+/// A fresh id for a workspace or todo. Every new id goes through here.
+pub fn new_id() -> String {
+    ID_GENERATOR.with(|generator| match generator.borrow_mut().as_mut() {
+        Some(id_generator) => id_generator(),
+        None => Uuid::new_v4().to_string(),
+    })
+}
+
+/// Where the store is kept between runs, picked with the `storage` setting.
+pub trait StoreBackend {
+    /// The file holding the data. A fresh install has none yet.
+    fn path(&self) -> &Path;
+    fn load(&self) -> io::Result<Store>;
+    fn save(&self, store: &Store) -> io::Result<()>;
+}
+
+/// The default storage, the whole store as one JSON file with numbered
+/// backups next to it.
+pub struct JsonBackend {
+    pub path: PathBuf,
+    pub backups: usize,
+}
+
+impl StoreBackend for JsonBackend {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> io::Result<Store> {
+        Store::from_json_file(&self.path)
+    }
+
+    fn save(&self, store: &Store) -> io::Result<()> {
+        config::save_data_file(store, &self.path, self.backups)
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Todo {
     pub id: String,
     pub description: String,
+    #[serde(default, with = "timestamp")]
     pub due: Option<SystemTime>,
     pub effort: usize,
     pub urgency: usize,
-    pub pending: bool,
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Hidden from the todos pane until then.
+    #[serde(default, with = "timestamp")]
+    pub snooze_until: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Completion flag written before `status` existed. Only read so old
+    /// files can be migrated, `true` maps to `Todo` and `false` to `Done`.
+    #[serde(default, skip_serializing)]
+    pub pending: Option<bool>,
     pub children: Vec<Todo>,
 }
 
+impl Todo {
+    /// A pending todo with a fresh id and default fields.
+    pub fn new(description: &str) -> Self {
+        Todo {
+            id: new_id(),
+            description: description.to_string(),
+            due: None,
+            effort: 0,
+            urgency: 0,
+            status: Status::Todo,
+            pinned: false,
+            snooze_until: None,
+            tags: Vec::new(),
+            pending: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Renders the todo and its children as a Markdown checklist, the way
+    /// [`Workspace::to_markdown`] writes them.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        write_todos_markdown(std::slice::from_ref(self), &mut markdown);
+        markdown
+    }
+
+    /// Number of todos in the todo and its children, counting itself.
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(todo) = stack.pop() {
+            size += 1;
+            stack.extend(&todo.children);
+        }
+        size
+    }
+
+    /// Number of levels in the todo and its children, counting itself.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((todo, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            stack.extend(todo.children.iter().map(|t| (t, depth + 1)));
+        }
+        deepest
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Todo,
+    Doing,
+    Done,
+}
+
+impl Status {
+    /// The status `c` moves a todo to: Todo -> Doing -> Done -> Todo.
+    pub fn next(self) -> Self {
+        match self {
+            Status::Todo => Status::Doing,
+            Status::Doing => Status::Done,
+            Status::Done => Status::Todo,
+        }
+    }
+
+    pub fn is_done(self) -> bool {
+        self == Status::Done
+    }
+}
+
+/// Stores an `Option<SystemTime>` as an ISO-8601 string so the data file stays
+/// readable. Files written before this used serde's default
+/// `{ secs_since_epoch, nanos_since_epoch }` form, which is still accepted on
+/// load and rewritten as a string on the next save.
+mod timestamp {
+    use chrono::{DateTime, Utc};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Iso(String),
+        EpochSeconds(i64),
+        Legacy {
+            secs_since_epoch: u64,
+            nanos_since_epoch: u32,
+        },
+    }
+
+    pub fn serialize<S: Serializer>(time: &Option<SystemTime>, s: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => s.serialize_some(&DateTime::<Utc>::from(*time).to_rfc3339()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SystemTime>, D::Error> {
+        let time = match Option::<Repr>::deserialize(d)? {
+            None => return Ok(None),
+            Some(Repr::Iso(s)) => DateTime::parse_from_rfc3339(&s)
+                .map_err(de::Error::custom)?
+                .into(),
+            Some(Repr::EpochSeconds(secs)) => DateTime::<Utc>::from_timestamp(secs, 0)
+                .ok_or_else(|| de::Error::custom("timestamp out of range"))?
+                .into(),
+            Some(Repr::Legacy {
+                secs_since_epoch,
+                nanos_since_epoch,
+            }) => UNIX_EPOCH + Duration::new(secs_since_epoch, nanos_since_epoch),
+        };
+
+        Ok(Some(time))
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Workspace {
     pub id: String,
     pub description: String,
+    /// Accent color name or hex code, falls back to the theme when unset.
+    #[serde(default)]
+    pub color: Option<String>,
     pub children: Vec<Workspace>,
     pub todos: Vec<Todo>,
+    /// Ids of the todos left expanded, so the tree looks the same on the next
+    /// start. Ids that no longer exist are ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub opened_todos: Vec<String>,
+}
+
+/// A single line of the plain text outline shared by `list` and the `Y`
+/// clipboard copy, which [`parse_outline`] reads back.
+pub fn outline_line(depth: usize, status: Status, description: &str) -> String {
+    let marker = if status.is_done() { "[x]" } else { "[ ]" };
+    format!("{}{} {}\n", "  ".repeat(depth), marker, description)
+}
+
+/// Parses one todo per line, nesting each line under the closest less indented
+/// line above it. Blank lines are skipped. A leading `- ` and a `[ ]` or `[x]`
+/// checkbox are stripped, `[x]` marking the todo as done, so the text copied
+/// with `Y` can be read back.
+pub fn parse_outline(text: &str) -> Vec<Todo> {
+    let mut roots = Vec::new();
+    // Todos whose children may still follow, with their indentation
+    let mut open: Vec<(usize, Todo)> = Vec::new();
+
+    for line in text.lines() {
+        let content = line.trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        let indent = line
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+
+        while open.last().is_some_and(|(i, _)| *i >= indent) {
+            let (_, todo) = open.pop().unwrap();
+            attach(&mut open, &mut roots, todo);
+        }
+
+        open.push((indent, parse_line(content)));
+    }
+
+    while let Some((_, todo)) = open.pop() {
+        attach(&mut open, &mut roots, todo);
+    }
+
+    roots
+}
+
+fn attach(open: &mut [(usize, Todo)], roots: &mut Vec<Todo>, todo: Todo) {
+    match open.last_mut() {
+        Some((_, parent)) => parent.children.push(todo),
+        None => roots.push(todo),
+    }
+}
+
+fn parse_line(line: &str) -> Todo {
+    let line = line.strip_prefix("- ").unwrap_or(line);
+
+    let (status, description) = if let Some(rest) = line.strip_prefix("[ ] ") {
+        (Status::Todo, rest)
+    } else if let Some(rest) = line
+        .strip_prefix("[x] ")
+        .or_else(|| line.strip_prefix("[X] "))
+    {
+        (Status::Done, rest)
+    } else {
+        (Status::Todo, line)
+    };
+
+    Todo {
+        status,
+        ..Todo::new(description.trim())
+    }
+}
+
+/// Parses the checklist of `markdown`, nesting items by indentation however
+/// ragged it is. Lines other than `- ` or `* ` list items, such as headings,
+/// are skipped. The `(priority 2, due ...)` written by
+/// [`Workspace::to_markdown`] is read back.
+pub fn parse_markdown(markdown: &str) -> Vec<Todo> {
+    let items: String = markdown
+        .lines()
+        .filter_map(|line| {
+            let item = line.trim_start();
+            let indent = &line[..line.len() - item.len()];
+            let item = item
+                .strip_prefix("- ")
+                .or_else(|| item.strip_prefix("* "))?;
+            Some(format!("{}- {}\n", indent, item))
+        })
+        .collect();
+
+    let mut todos = parse_outline(&items);
+    let mut stack: Vec<&mut Todo> = todos.iter_mut().collect();
+    while let Some(todo) = stack.pop() {
+        read_markdown_details(todo);
+        stack.extend(todo.children.iter_mut());
+    }
+    todos
+}
+
+/// Moves a trailing `(priority N, due D)` from the description onto the todo.
+/// The description is left alone unless every part of it parses.
+fn read_markdown_details(todo: &mut Todo) {
+    let Some(details) = todo
+        .description
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+        .map(|(_, details)| details)
+    else {
+        return;
+    };
+
+    let (mut urgency, mut due) = (todo.urgency, todo.due);
+    for detail in details.split(", ") {
+        if let Some(value) = detail.strip_prefix("priority ") {
+            match value.parse() {
+                Ok(value) => urgency = value,
+                Err(_) => return,
+            }
+        } else if let Some(value) = detail.strip_prefix("due ") {
+            match dates::parse_due(value) {
+                Ok(value) => due = Some(value),
+                Err(_) => return,
+            }
+        } else {
+            return;
+        }
+    }
+
+    let length = todo.description.len() - details.len() - " ()".len();
+    todo.description.truncate(length);
+    todo.urgency = urgency;
+    todo.due = due;
 }
 
 impl Workspace {
+    /// Renders the workspace as Markdown, with child workspaces as deeper
+    /// headings and todos as nested checklists. A todo's priority, when above
+    /// the lowest, and due date follow it in parentheses.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        self.write_markdown(1, &mut markdown);
+        markdown
+    }
+
+    /// Fills `opened_todos` of this workspace and its children with the ids
+    /// in `opened` that belong to each of them.
+    pub fn record_opened_todos(&mut self, opened: &HashSet<&str>) {
+        self.opened_todos.clear();
+        let mut stack: Vec<&Todo> = self.todos.iter().collect();
+        while let Some(todo) = stack.pop() {
+            if opened.contains(todo.id.as_str()) {
+                self.opened_todos.push(todo.id.clone());
+            }
+            stack.extend(&todo.children);
+        }
+
+        for child in &mut self.children {
+            child.record_opened_todos(opened);
+        }
+    }
+
+    fn write_markdown(&self, level: usize, markdown: &mut String) {
+        markdown.push_str(&format!(
+            "{} {}\n\n",
+            "#".repeat(level.min(6)),
+            self.description
+        ));
+
+        write_todos_markdown(&self.todos, markdown);
+        if !self.todos.is_empty() {
+            markdown.push('\n');
+        }
+
+        for child in &self.children {
+            child.write_markdown(level + 1, markdown);
+        }
+    }
+
     pub fn get_mut_todo(&mut self, selected: &[String]) -> Option<&mut Todo> {
         let mut selected_iter = selected.iter();
         let first_item = selected_iter.next()?;
 
         let mut todo = self.todos.iter_mut().find(|t| t.id == *first_item)?;
 
-        while let Some(id) = selected_iter.next() {
+        for id in selected_iter {
             todo = todo.children.iter_mut().find(|t| t.id == *id)?;
         }
 
@@ -45,7 +412,7 @@ impl Workspace {
 
         let mut todo = self.todos.iter().find(|t| t.id == *first_item)?;
 
-        while let Some(id) = selected_iter.next() {
+        for id in selected_iter {
             todo = todo.children.iter().find(|t| t.id == *id)?;
         }
 
@@ -53,19 +420,160 @@ impl Workspace {
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+/// Written to the data file so older files can be told apart and upgraded.
+/// Bump it along with a new step in [`Store::migrate`].
+pub const STORE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
 pub struct Store {
+    /// Files from before versioning have none and read as 0.
+    #[serde(default)]
+    pub version: u32,
     pub workspaces: Vec<Workspace>,
 }
 
+impl Default for Store {
+    fn default() -> Self {
+        Store {
+            version: STORE_VERSION,
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+/// Appends `todos` and their children as nested checklist items. A todo's
+/// priority, when above the lowest, and due date follow it in parentheses.
+fn write_todos_markdown(todos: &[Todo], markdown: &mut String) {
+    let mut stack: Vec<(&Todo, usize)> = todos.iter().rev().map(|t| (t, 0)).collect();
+    while let Some((todo, depth)) = stack.pop() {
+        let marker = if todo.status.is_done() { "x" } else { " " };
+        let mut details = Vec::new();
+        if todo.urgency > 0 {
+            details.push(format!("priority {}", todo.urgency));
+        }
+        if let Some(due) = todo.due {
+            details.push(format!("due {}", dates::format_due(due)));
+        }
+        let details = if details.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", details.join(", "))
+        };
+        markdown.push_str(&format!(
+            "{}- [{}] {}{}\n",
+            "  ".repeat(depth),
+            marker,
+            todo.description,
+            details
+        ));
+        stack.extend(todo.children.iter().rev().map(|t| (t, depth + 1)));
+    }
+}
+
 impl Store {
+    /// Every workspace rendered with [`Workspace::to_markdown`], one after
+    /// the other.
+    pub fn to_markdown(&self) -> String {
+        self.workspaces.iter().map(Workspace::to_markdown).collect()
+    }
+
+    /// Store seeded on the very first run so a fresh install doesn't open to
+    /// a blank screen.
+    pub fn example() -> Self {
+        fn todo(description: &str, urgency: usize, status: Status, children: Vec<Todo>) -> Todo {
+            Todo {
+                urgency,
+                status,
+                children,
+                ..Todo::new(description)
+            }
+        }
+
+        Self {
+            version: STORE_VERSION,
+            workspaces: vec![Workspace {
+                id: new_id(),
+                description: "Welcome".to_string(),
+                color: None,
+                children: Vec::new(),
+                opened_todos: Vec::new(),
+                todos: vec![
+                    todo(
+                        "Getting started: j/k to move, Tab to switch panes, q to quit",
+                        0,
+                        Status::Todo,
+                        Vec::new(),
+                    ),
+                    todo(
+                        "Press c to cycle this todo through doing and done",
+                        0,
+                        Status::Todo,
+                        Vec::new(),
+                    ),
+                    todo(
+                        "Raise or lower priority with + and _",
+                        3,
+                        Status::Todo,
+                        Vec::new(),
+                    ),
+                    todo(
+                        "Todos can be nested, press l to expand this one",
+                        1,
+                        Status::Doing,
+                        vec![
+                            todo("A adds a child todo", 0, Status::Done, Vec::new()),
+                            todo("a adds a sibling todo", 0, Status::Todo, Vec::new()),
+                        ],
+                    ),
+                ],
+            }],
+        }
+    }
+
     pub fn from_json_file(path: &PathBuf) -> io::Result<Self> {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
-        let store = serde_json::from_reader(reader)?;
+        let mut store: Store = serde_json::from_reader(reader)?;
+        store.migrate()?;
         Ok(store)
     }
 
+    /// Upgrades a store read from an older file to [`STORE_VERSION`], one
+    /// version at a time. Files from a newer doneit are refused, saving them
+    /// would drop whatever this version doesn't know about.
+    fn migrate(&mut self) -> io::Result<()> {
+        if self.version > STORE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "written by a newer doneit (data version {}, this one reads up to {})",
+                    self.version, STORE_VERSION
+                ),
+            ));
+        }
+        if self.version < 1 {
+            // Turn the `pending` flag written before `status` existed into
+            // a status, so the subcommands see it too and not just the TUI
+            fn migrate_pending(todos: &mut [Todo]) {
+                for todo in todos {
+                    match todo.pending.take() {
+                        Some(true) => todo.status = Status::Todo,
+                        Some(false) => todo.status = Status::Done,
+                        None => {}
+                    }
+                    migrate_pending(&mut todo.children);
+                }
+            }
+            let mut stack: Vec<&mut Workspace> = self.workspaces.iter_mut().collect();
+            while let Some(workspace) = stack.pop() {
+                migrate_pending(&mut workspace.todos);
+                stack.extend(workspace.children.iter_mut());
+            }
+        }
+        self.version = STORE_VERSION;
+        Ok(())
+    }
+
     pub fn to_json_file(&self, path: &PathBuf) -> io::Result<()> {
         let file = File::create(path)?;
         let writer = BufWriter::new(file);
@@ -79,20 +587,101 @@ impl Store {
 
         let mut workspace = self.workspaces.iter_mut().find(|w| w.id == *first_item)?;
 
-        while let Some(id) = selection_iter.next() {
+        for id in selection_iter {
             workspace = workspace.children.iter_mut().find(|w| w.id == *id)?;
         }
 
         Some(workspace)
     }
 
+    /// Gives a new id to every workspace or todo whose id was already seen,
+    /// which can only come from editing or merging files by hand. Lookups by
+    /// id would otherwise find the wrong item. Returns how many were changed.
+    pub fn reassign_duplicate_ids(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let mut reassigned = 0;
+        let mut reassign = |id: &mut String| {
+            if !seen.insert(id.clone()) {
+                *id = new_id();
+                seen.insert(id.clone());
+                reassigned += 1;
+            }
+        };
+
+        let mut workspaces: Vec<&mut Workspace> = self.workspaces.iter_mut().collect();
+        while let Some(workspace) = workspaces.pop() {
+            reassign(&mut workspace.id);
+            let mut todos: Vec<&mut Todo> = workspace.todos.iter_mut().collect();
+            while let Some(todo) = todos.pop() {
+                reassign(&mut todo.id);
+                todos.extend(todo.children.iter_mut());
+            }
+            workspaces.extend(workspace.children.iter_mut());
+        }
+        reassigned
+    }
+
+    /// `opened_todos` of every workspace.
+    pub fn opened_todo_ids(&self) -> HashSet<&str> {
+        let mut ids = HashSet::new();
+        let mut stack: Vec<&Workspace> = self.workspaces.iter().collect();
+        while let Some(workspace) = stack.pop() {
+            ids.extend(workspace.opened_todos.iter().map(String::as_str));
+            stack.extend(&workspace.children);
+        }
+        ids
+    }
+
+    /// Returns the first workspace, at any depth, with the given name.
+    pub fn workspace_by_name(&self, name: &str) -> Option<&Workspace> {
+        fn find<'a>(workspaces: &'a [Workspace], name: &str) -> Option<&'a Workspace> {
+            workspaces.iter().find_map(|workspace| {
+                if workspace.description == name {
+                    Some(workspace)
+                } else {
+                    find(&workspace.children, name)
+                }
+            })
+        }
+
+        find(&self.workspaces, name)
+    }
+
+    /// Returns the first workspace, at any depth, with the given name,
+    /// creating it at the top level if there is none.
+    pub fn workspace_by_name_mut(&mut self, name: &str) -> &mut Workspace {
+        fn find<'a>(workspaces: &'a mut [Workspace], name: &str) -> Option<&'a mut Workspace> {
+            for workspace in workspaces {
+                if workspace.description == name {
+                    return Some(workspace);
+                }
+                if let Some(found) = find(&mut workspace.children, name) {
+                    return Some(found);
+                }
+            }
+            None
+        }
+
+        if find(&mut self.workspaces, name).is_none() {
+            self.workspaces.push(Workspace {
+                id: new_id(),
+                description: name.to_string(),
+                color: None,
+                children: Vec::new(),
+                todos: Vec::new(),
+                opened_todos: Vec::new(),
+            });
+        }
+        find(&mut self.workspaces, name).unwrap()
+    }
+
     pub fn get_workflow(&self, selection: &[String]) -> Option<&Workspace> {
         let mut selection_iter = selection.iter();
         let first_item = selection_iter.next()?;
 
         let mut workspace = self.workspaces.iter().find(|w| w.id == *first_item)?;
 
-        while let Some(id) = selection_iter.next() {
+        for id in selection_iter {
             workspace = workspace.children.iter().find(|w| w.id == *id)?;
         }
 
@@ -100,10 +689,28 @@ impl Store {
     }
 }
 
+/// Workspace trees saved with `:save-template`, by name.
+pub type Templates = BTreeMap<String, Workspace>;
+
+pub fn load_templates(path: &PathBuf) -> io::Result<Templates> {
+    if !path.exists() {
+        return Ok(Templates::new());
+    }
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+pub fn save_templates(path: &PathBuf, templates: &Templates) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, templates)?;
+    Ok(())
+}
+
 #[derive(Clone)]
 pub struct WorkspaceItem {
     pub id: String,
     pub description: String,
+    pub color: Option<String>,
     pub todos: Vec<DefaultKey>,
     pub children: Vec<DefaultKey>,
 }
@@ -115,7 +722,10 @@ pub struct TodoItem {
     pub due: Option<SystemTime>,
     pub effort: usize,
     pub urgency: usize,
-    pub pending: bool,
+    pub status: Status,
+    pub pinned: bool,
+    pub snooze_until: Option<SystemTime>,
+    pub tags: Vec<String>,
     pub children: Vec<DefaultKey>,
 }
 
@@ -126,24 +736,39 @@ pub struct SlotMapStore {
 }
 
 impl SlotMapStore {
-    fn add_todo(todos_map: &mut SlotMap<DefaultKey, TodoItem>, t: &Todo) -> DefaultKey {
-        let mut todo_item = TodoItem {
+    /// The item for `t`, without its children.
+    fn todo_item(t: &Todo) -> TodoItem {
+        TodoItem {
             id: t.id.clone(),
             effort: t.effort,
             urgency: t.urgency,
-            pending: t.pending,
+            status: match t.pending {
+                Some(true) => Status::Todo,
+                Some(false) => Status::Done,
+                None => t.status,
+            },
+            pinned: t.pinned,
+            snooze_until: t.snooze_until,
+            tags: t.tags.clone(),
             children: Vec::new(),
             description: t.description.clone(),
             due: t.due,
-        };
+        }
+    }
 
-        todo_item.children = t
-            .children
-            .iter()
-            .map(|t| Self::add_todo(todos_map, t))
-            .collect();
+    // The trees are walked with explicit stacks rather than recursion so
+    // very deep data can't overflow the call stack on load.
 
-        return todos_map.insert(todo_item);
+    fn add_todo(todos_map: &mut SlotMap<DefaultKey, TodoItem>, t: &Todo) -> DefaultKey {
+        let root = todos_map.insert(Self::todo_item(t));
+        let mut stack: Vec<(&Todo, DefaultKey)> =
+            t.children.iter().rev().map(|child| (child, root)).collect();
+        while let Some((todo, parent)) = stack.pop() {
+            let key = todos_map.insert(Self::todo_item(todo));
+            todos_map[parent].children.push(key);
+            stack.extend(todo.children.iter().rev().map(|child| (child, key)));
+        }
+        root
     }
 
     fn add_workspace(
@@ -151,26 +776,38 @@ impl SlotMapStore {
         todos_map: &mut SlotMap<DefaultKey, TodoItem>,
         w: &Workspace,
     ) -> DefaultKey {
-        let mut ws_item = WorkspaceItem {
+        let ws_item = |w: &Workspace| WorkspaceItem {
             id: w.id.clone(),
             description: w.description.clone(),
+            color: w.color.clone(),
             todos: Vec::new(),
             children: Vec::new(),
         };
 
-        ws_item.children = w
-            .children
-            .iter()
-            .map(|c| Self::add_workspace(workspace_map, todos_map, c))
-            .collect();
+        let root = workspace_map.insert(ws_item(w));
+        let mut stack = vec![(w, root)];
+        while let Some((w, key)) = stack.pop() {
+            for child in &w.children {
+                let child_key = workspace_map.insert(ws_item(child));
+                workspace_map[key].children.push(child_key);
+                stack.push((child, child_key));
+            }
+            for t in &w.todos {
+                let todo_key = Self::add_todo(todos_map, t);
+                workspace_map[key].todos.push(todo_key);
+            }
+        }
+        root
+    }
 
-        ws_item.todos = w
-            .todos
-            .iter()
-            .map(|t| Self::add_todo(todos_map, t))
-            .collect();
+    /// Adds a todo tree to the map, without attaching it anywhere.
+    pub fn insert_todo(&mut self, todo: &Todo) -> DefaultKey {
+        Self::add_todo(&mut self.todos_map, todo)
+    }
 
-        return workspace_map.insert(ws_item);
+    /// Adds a workspace tree to the maps, without attaching it anywhere.
+    pub fn insert_workspace(&mut self, workspace: &Workspace) -> DefaultKey {
+        Self::add_workspace(&mut self.workspaces_map, &mut self.todos_map, workspace)
     }
 
     pub fn from_store(store: &Store) -> Self {
@@ -189,35 +826,89 @@ impl SlotMapStore {
         }
     }
 
-    fn create_todo(&self, key: DefaultKey) -> Todo {
-        let t = self.todos_map.get(key).unwrap();
-        Todo {
-            id: t.id.clone(),
-            description: t.description.clone(),
-            children: t.children.iter().map(|k| self.create_todo(*k)).collect(),
-            due: t.due,
-            effort: t.effort,
-            urgency: t.urgency,
-            pending: t.pending,
+    pub fn create_todo(&self, key: DefaultKey) -> Todo {
+        let todo = |key: DefaultKey| {
+            let t = self.todos_map.get(key).unwrap();
+            let todo = Todo {
+                id: t.id.clone(),
+                description: t.description.clone(),
+                children: Vec::new(),
+                due: t.due,
+                effort: t.effort,
+                urgency: t.urgency,
+                status: t.status,
+                pinned: t.pinned,
+                snooze_until: t.snooze_until,
+                tags: t.tags.clone(),
+                pending: None,
+            };
+            (todo, t.children.iter())
+        };
+
+        // Each todo with the keys of its children still to be created
+        let mut stack = vec![todo(key)];
+        loop {
+            let (_, children) = stack.last_mut().unwrap();
+            match children.next() {
+                Some(child) => stack.push(todo(*child)),
+                None => {
+                    let (done, _) = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some((parent, _)) => parent.children.push(done),
+                        None => return done,
+                    }
+                }
+            }
         }
     }
 
-    fn create_workspace(&self, key: DefaultKey) -> Workspace {
-        let ws = self.workspaces_map.get(key).unwrap();
-        Workspace {
-            id: ws.id.clone(),
-            description: ws.description.clone(),
-            children: ws
-                .children
-                .iter()
-                .map(|k| self.create_workspace(*k))
-                .collect(),
-            todos: ws.todos.iter().map(|k| self.create_todo(*k)).collect(),
+    pub fn create_workspace(&self, key: DefaultKey) -> Workspace {
+        let workspace = |key: DefaultKey| {
+            let ws = self.workspaces_map.get(key).unwrap();
+            let workspace = Workspace {
+                id: ws.id.clone(),
+                description: ws.description.clone(),
+                color: ws.color.clone(),
+                children: Vec::new(),
+                todos: ws.todos.iter().map(|k| self.create_todo(*k)).collect(),
+                opened_todos: Vec::new(),
+            };
+            (workspace, ws.children.iter())
+        };
+
+        let mut stack = vec![workspace(key)];
+        loop {
+            let (_, children) = stack.last_mut().unwrap();
+            match children.next() {
+                Some(child) => stack.push(workspace(*child)),
+                None => {
+                    let (done, _) = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some((parent, _)) => parent.children.push(done),
+                        None => return done,
+                    }
+                }
+            }
         }
     }
 
+    /// Whether `candidate` is `ancestor` or anywhere in its subtree. Moving
+    /// `ancestor` under such a todo would create a cycle, which the recursive
+    /// tree walks would never get out of.
+    pub fn is_todo_descendant(&self, ancestor: DefaultKey, candidate: DefaultKey) -> bool {
+        let mut stack = vec![ancestor];
+        while let Some(key) = stack.pop() {
+            if key == candidate {
+                return true;
+            }
+            stack.extend(&self.todos_map.get(key).unwrap().children);
+        }
+        false
+    }
+
     pub fn get_store(&self) -> Store {
         Store {
+            version: STORE_VERSION,
             workspaces: self
                 .root_workspaces
                 .iter()
@@ -226,3 +917,164 @@ impl SlotMapStore {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out `id-1`, `id-2`, ... on this thread.
+    fn sequential_ids() {
+        let mut next = 0;
+        set_id_generator(Box::new(move || {
+            next += 1;
+            format!("id-{}", next)
+        }));
+    }
+
+    #[test]
+    fn new_todos_take_their_ids_from_the_generator() {
+        sequential_ids();
+        let todos = parse_outline("a\n  b\n");
+        assert_eq!(todos[0].id, "id-1");
+        assert_eq!(todos[0].children[0].id, "id-2");
+        assert_eq!(Todo::new("c").id, "id-3");
+    }
+
+    #[test]
+    fn is_todo_descendant_covers_the_whole_subtree() {
+        let mut store = Store::default();
+        store
+            .workspace_by_name_mut("Work")
+            .todos
+            .extend(parse_outline("a\n  b\n    c\nd\n"));
+        let slot_map_store = SlotMapStore::from_store(&store);
+        let key = |description: &str| {
+            slot_map_store
+                .todos_map
+                .iter()
+                .find(|(_, todo)| todo.description == description)
+                .unwrap()
+                .0
+        };
+
+        assert!(slot_map_store.is_todo_descendant(key("a"), key("a")));
+        assert!(slot_map_store.is_todo_descendant(key("a"), key("b")));
+        assert!(slot_map_store.is_todo_descendant(key("a"), key("c")));
+        assert!(!slot_map_store.is_todo_descendant(key("b"), key("a")));
+        assert!(!slot_map_store.is_todo_descendant(key("a"), key("d")));
+    }
+
+    #[test]
+    fn workspace_to_markdown_nests_todos_and_workspaces() {
+        let mut store = Store::default();
+        let work = store.workspace_by_name_mut("Work");
+        work.todos = parse_outline("a\n  [x] b\n    c\nd\n");
+        work.todos[0].urgency = 2;
+        work.todos[1].due = dates::parse_due("2024-12-31").ok();
+        work.todos[0].children[0].children[0].urgency = 1;
+        work.todos[0].children[0].children[0].due = dates::parse_due("2025-01-02 09:30").ok();
+        work.children.push(Workspace {
+            id: new_id(),
+            description: "Notes".to_string(),
+            color: None,
+            children: Vec::new(),
+            todos: parse_outline("[x] e\n"),
+            opened_todos: Vec::new(),
+        });
+
+        assert_eq!(
+            store.workspaces[0].to_markdown(),
+            concat!(
+                "# Work\n",
+                "\n",
+                "- [ ] a (priority 2)\n",
+                "  - [x] b\n",
+                "    - [ ] c (priority 1, due 2025-01-02 09:30)\n",
+                "- [ ] d (due 2024-12-31)\n",
+                "\n",
+                "## Notes\n",
+                "\n",
+                "- [x] e\n",
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn import_markdown_only_rewrites_leading_markers() {
+        let todos = parse_markdown("# Work\n* [ ] a * b\n  - [x] c * d\n\ttext\n* e\n");
+
+        assert_eq!(todos.iter().map(Todo::size).sum::<usize>(), 3);
+        assert_eq!(todos[0].description, "a * b");
+        assert_eq!(todos[0].children[0].description, "c * d");
+        assert!(todos[0].children[0].status.is_done());
+        assert_eq!(todos[1].description, "e");
+    }
+
+    #[test]
+    fn unversioned_files_are_migrated_and_stamped() {
+        let mut store: Store = serde_json::from_str(
+            r#"{"workspaces":[{"id":"w","description":"Work","children":[],"todos":[
+                {"id":"a","description":"a","effort":0,"urgency":0,"pending":false,"children":[
+                    {"id":"b","description":"b","effort":0,"urgency":0,"pending":true,"children":[]}
+                ]}
+            ]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(store.version, 0);
+
+        store.migrate().unwrap();
+        assert_eq!(store.version, STORE_VERSION);
+        let a = &store.workspaces[0].todos[0];
+        assert!(a.status == Status::Done && a.pending.is_none());
+        assert!(a.children[0].status == Status::Todo && a.children[0].pending.is_none());
+    }
+
+    #[test]
+    fn files_from_a_newer_version_are_refused() {
+        let mut store: Store = serde_json::from_str(&format!(
+            r#"{{"version":{},"workspaces":[]}}"#,
+            STORE_VERSION + 1
+        ))
+        .unwrap();
+        assert!(store.migrate().is_err());
+        assert_eq!(store.version, STORE_VERSION + 1);
+    }
+
+    #[test]
+    fn reassign_duplicate_ids_only_touches_repeats() {
+        sequential_ids();
+        let todo = |id: &str, children: &str| {
+            format!(
+                r#"{{"id":"{}","description":"{}","effort":0,"urgency":0,"children":[{}]}}"#,
+                id, id, children
+            )
+        };
+        let payload = format!(
+            r#"{{"version":1,"workspaces":[
+                {{"id":"w1","description":"One","children":[],"todos":[{},{}]}},
+                {{"id":"w1","description":"Two","children":[],"todos":[{}]}}
+            ]}}"#,
+            todo("a", &todo("b", "")),
+            todo("a", ""),
+            todo("c", &todo("b", "")),
+        );
+        let mut store: Store = serde_json::from_str(&payload).unwrap();
+
+        assert_eq!(store.reassign_duplicate_ids(), 3);
+
+        let mut ids = Vec::new();
+        let mut workspaces: Vec<&Workspace> = store.workspaces.iter().collect();
+        while let Some(workspace) = workspaces.pop() {
+            ids.push(workspace.id.as_str());
+            let mut todos: Vec<&Todo> = workspace.todos.iter().collect();
+            while let Some(todo) = todos.pop() {
+                ids.push(todo.id.as_str());
+                todos.extend(&todo.children);
+            }
+            workspaces.extend(&workspace.children);
+        }
+        ids.sort_unstable();
+        assert_eq!(ids, ["a", "b", "c", "id-1", "id-2", "id-3", "w1"]);
+    }
+}